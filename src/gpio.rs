@@ -77,6 +77,29 @@ pub trait TouchPin: Pin {
     fn touch_channel(&self) -> touch_pad_t;
 }
 
+/// Logic level of a pin
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Level {
+    Low = 0,
+    High = 1,
+}
+
+impl From<bool> for Level {
+    fn from(high: bool) -> Self {
+        if high {
+            Level::High
+        } else {
+            Level::Low
+        }
+    }
+}
+
+impl From<Level> for bool {
+    fn from(level: Level) -> Self {
+        level == Level::High
+    }
+}
+
 pub struct Input;
 
 pub struct Output;
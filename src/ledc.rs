@@ -0,0 +1,4079 @@
+//! LED Control peripheral (which also creates PWM signals for other purposes)
+//!
+//! Interface to the [LED Control (LEDC)
+//! peripheral](https://docs.espressif.com/projects/esp-idf/en/latest/esp32/api-reference/peripherals/ledc.html)
+//!
+//! This is an initial implementation supporting the generation of PWM signals
+//! and hardware fading of their duty cycle.
+//!
+//! # Examples
+//!
+//! Create a 25 kHz PWM signal with 75 % duty cycle on GPIO 1
+//! ```
+//! use embedded_hal::pwm::blocking::PwmPin;
+//! use esp_idf_hal::ledc::{config::TimerConfig, Channel, Timer};
+//! use esp_idf_hal::peripherals::Peripherals;
+//! use esp_idf_hal::prelude::*;
+//!
+//! let peripherals = Peripherals::take().unwrap();
+//! let config = TimerConfig::default().frequency(25.kHz().into());
+//! let timer = Timer::new(peripherals.ledc.timer0, &config)?;
+//! let mut channel = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+//!
+//! let max_duty = channel.get_max_duty()?;
+//! channel.set_duty(max_duty * 3 / 4)?;
+//! ```
+//!
+//! Channels only borrow their timer, so multiple channels can share the same
+//! timer and thus frequency and resolution. For example to drive an RGB LED on
+//! GPIOs 1, 2 and 3
+//! ```
+//! use embedded_hal::pwm::blocking::PwmPin;
+//! use esp_idf_hal::ledc::{config::TimerConfig, Channel, Timer};
+//! use esp_idf_hal::peripherals::Peripherals;
+//! use esp_idf_hal::prelude::*;
+//!
+//! let peripherals = Peripherals::take().unwrap();
+//! let config = TimerConfig::default().frequency(25.kHz().into());
+//! let timer = Timer::new(peripherals.ledc.timer0, &config)?;
+//! let mut red = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+//! let mut green = Channel::new(peripherals.ledc.channel1, &timer, peripherals.pins.gpio2)?;
+//! let mut blue = Channel::new(peripherals.ledc.channel2, &timer, peripherals.pins.gpio3)?;
+//!
+//! let max_duty = red.get_max_duty()?;
+//! red.set_duty(max_duty)?;
+//! green.set_duty(max_duty / 2)?;
+//! blue.set_duty(0)?;
+//! ```
+//!
+//! Each frequency needs a timer of its own as a timer determines frequency
+//! and resolution of all of its channels. For example three outputs at 1, 2
+//! and 5 kHz use three timers
+//! ```
+//! use esp_idf_hal::ledc::{config::TimerConfig, Channel, Timer};
+//! use esp_idf_hal::peripherals::Peripherals;
+//! use esp_idf_hal::prelude::*;
+//!
+//! let peripherals = Peripherals::take().unwrap();
+//! let slow = Timer::new(peripherals.ledc.timer0, &TimerConfig::default().frequency(1.kHz().into()))?;
+//! let medium = Timer::new(peripherals.ledc.timer1, &TimerConfig::default().frequency(2.kHz().into()))?;
+//! let fast = Timer::new(peripherals.ledc.timer2, &TimerConfig::default().frequency(5.kHz().into()))?;
+//!
+//! let mut a = Channel::new(peripherals.ledc.channel0, &slow, peripherals.pins.gpio1)?;
+//! let mut b = Channel::new(peripherals.ledc.channel1, &medium, peripherals.pins.gpio2)?;
+//! let mut c = Channel::new(peripherals.ledc.channel2, &fast, peripherals.pins.gpio3)?;
+//! ```
+//!
+//! The hardware timers are represented by the distinct types
+//! [`TIMER0`] to [`TIMER3`], so running out of them is a compile error.
+//! Configuring the same hardware timer twice by other means fails with
+//! [`LedcError::TimerInUse`] instead of silently changing the frequency of
+//! the channels bound to the first one. When the frequencies are only known
+//! at runtime, [`MultiTimer`](multi::MultiTimer) allocates the timers for
+//! them and binds channels by frequency.
+//!
+//! # Testing drivers
+//!
+//! This module calls into the LEDC driver of ESP-IDF and can't be swapped for
+//! an in-memory model: the whole crate, including the [`EspError`] returned
+//! from every fallible method, builds on `esp-idf-sys` and thus only for
+//! ESP-IDF targets. For unit testing on the host, write drivers against the
+//! traits implemented by [`Channel`] instead of the concrete type, i.e.
+//! [`PwmPin`] and [`StagedDuty`], and substitute a mock implementing them
+//! in tests.
+
+use crate::delay::TickType;
+use crate::gpio::{Level, OutputPin, Pin};
+use crate::mutex::Mutex;
+use crate::units::*;
+use core::borrow::Borrow;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+pub use chip::*;
+
+pub mod breathe;
+pub mod hbridge;
+pub mod multi;
+pub mod rgb;
+#[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+pub mod sequence;
+pub mod servo;
+pub mod soft;
+
+/// Duty cycle value of an output channel in timer counts
+pub type Duty = u32;
+
+const HPOINT: u32 = 0;
+
+/// Configured timers as a bit mask, one bit per timer of each speed mode
+static CONFIGURED_TIMERS: AtomicU32 = AtomicU32::new(0);
+
+/// Timers kept running in light sleep, using the same bits as
+/// `CONFIGURED_TIMERS`
+static SLEEP_TIMERS: AtomicU32 = AtomicU32::new(0);
+
+const TIMERS: u32 = ledc_timer_t_LEDC_TIMER_MAX as u32;
+
+fn timer_bit(speed_mode: ledc_mode_t, timer: ledc_timer_t) -> u32 {
+    1 << (speed_mode as u32 * TIMERS + timer as u32)
+}
+
+const SPEED_MODES: usize = ledc_mode_t_LEDC_SPEED_MODE_MAX as usize;
+
+const NO_CHANNELS: AtomicU32 = AtomicU32::new(0);
+
+/// Channels bound to each timer as bit masks, indexed like the bits of
+/// `CONFIGURED_TIMERS`
+static BOUND_CHANNELS: [AtomicU32; SPEED_MODES * TIMERS as usize] =
+    [NO_CHANNELS; SPEED_MODES * TIMERS as usize];
+
+/// Channels halted by [`Timer::stop_all_channels()`] as bit masks per speed
+/// mode. A channel stays halted until it gets enabled again.
+static HALTED_CHANNELS: [AtomicU32; SPEED_MODES] = [NO_CHANNELS; SPEED_MODES];
+
+/// Channels using the fade function as bit masks per speed mode, for
+/// checking this without taking `FADE_FUNC_USERS`
+static FADE_CHANNELS: [AtomicU32; SPEED_MODES] = [NO_CHANNELS; SPEED_MODES];
+
+fn bound_channels(speed_mode: ledc_mode_t, timer: ledc_timer_t) -> &'static AtomicU32 {
+    &BOUND_CHANNELS[(speed_mode as u32 * TIMERS + timer as u32) as usize]
+}
+
+fn channel_halted(speed_mode: ledc_mode_t, channel: ledc_channel_t) -> bool {
+    HALTED_CHANNELS[speed_mode as usize].load(Ordering::SeqCst) & (1 << channel) != 0
+}
+
+/// Number of channels currently using the fade function
+///
+/// This is guarded by [`crate::mutex::Mutex`], which builds upon the pthread
+/// implementation of ESP-IDF and doesn't require std. A plain atomic counter
+/// wouldn't do: a channel created concurrently to the first one could see a
+/// non-zero count and continue before the fade function has actually been
+/// installed, or an uninstall could race with an install.
+static FADE_FUNC_USERS: Mutex<usize> = Mutex::new(0);
+
+/// Registers a user of the fade function and installs it for the first one
+fn acquire_fade_func() -> Result<(), EspError> {
+    let mut users = FADE_FUNC_USERS.lock();
+    if *users == 0 {
+        esp!(unsafe { ledc_fade_func_install(0) })?;
+    }
+    *users += 1;
+    Ok(())
+}
+
+/// Unregisters a user of the fade function and uninstalls it after the last
+/// one is gone
+fn release_fade_func() {
+    let mut users = FADE_FUNC_USERS.lock();
+    *users -= 1;
+    if *users == 0 {
+        unsafe { ledc_fade_func_uninstall() };
+    }
+}
+
+/// Returns whether the fade function of the SDK is currently installed
+///
+/// Channels install it on demand and the last one gone uninstalls it again.
+/// So this is `false` after all channels have been released or dropped.
+pub fn fade_func_installed() -> bool {
+    *FADE_FUNC_USERS.lock() > 0
+}
+
+/// Returns which of the four hardware timers are currently configured by a
+/// [`Timer`]
+///
+/// This is a snapshot for libraries picking a timer which doesn't collide
+/// with the application. Index `n` is `true` if timer `n` is configured in
+/// any speed mode. Use [`Timer::is_configured()`] for checking a particular
+/// speed mode on chips supporting high speed mode.
+pub fn timers_in_use() -> [bool; 4] {
+    let configured = CONFIGURED_TIMERS.load(Ordering::SeqCst);
+
+    let mut in_use = [false; 4];
+    for (timer, slot) in in_use.iter_mut().enumerate() {
+        *slot = (0..SPEED_MODES).any(|speed_mode| {
+            configured & timer_bit(speed_mode as ledc_mode_t, timer as ledc_timer_t) != 0
+        });
+    }
+    in_use
+}
+
+/// Completion of hardware fades signalled by the fade end callback
+#[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+mod fade_end {
+    #[cfg(feature = "experimental")]
+    use core::future::Future;
+    #[cfg(feature = "experimental")]
+    use core::pin::Pin;
+    use core::ptr;
+    use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+    #[cfg(feature = "experimental")]
+    use core::task::{Context, Poll, Waker};
+
+    use esp_idf_sys::*;
+
+    #[cfg(feature = "experimental")]
+    use crate::interrupt;
+
+    const CHANNELS: usize = ledc_channel_t_LEDC_CHANNEL_MAX as usize;
+    const SPEED_MODES: usize = ledc_mode_t_LEDC_SPEED_MODE_MAX as usize;
+
+    /// State shared between a channel and its fade end callback running in
+    /// ISR context
+    pub(super) struct State {
+        done: AtomicBool,
+        completed: AtomicU32,
+        stop: AtomicBool,
+        idle_level: AtomicU32,
+        hold: AtomicBool,
+        hold_duty: AtomicU32,
+        /// `esp_timer` to start at the end of the next fade
+        timer: AtomicPtr<esp_timer>,
+        /// Whether the callback is about to start `timer`, for cancelling it
+        /// race-free
+        starting: AtomicBool,
+        #[cfg(feature = "experimental")]
+        waker: interrupt::Mutex<Option<Waker>>,
+    }
+
+    impl State {
+        const fn new() -> Self {
+            Self {
+                done: AtomicBool::new(false),
+                completed: AtomicU32::new(0),
+                stop: AtomicBool::new(false),
+                idle_level: AtomicU32::new(0),
+                hold: AtomicBool::new(false),
+                hold_duty: AtomicU32::new(0),
+                timer: AtomicPtr::new(ptr::null_mut()),
+                starting: AtomicBool::new(false),
+                #[cfg(feature = "experimental")]
+                waker: interrupt::Mutex::new(None),
+            }
+        }
+    }
+
+    const NEW_STATE: State = State::new();
+    const NEW_STATES: [State; CHANNELS] = [NEW_STATE; CHANNELS];
+
+    static STATES: [[State; CHANNELS]; SPEED_MODES] = [NEW_STATES; SPEED_MODES];
+
+    fn state(speed_mode: ledc_mode_t, channel: ledc_channel_t) -> &'static State {
+        &STATES[speed_mode as usize][channel as usize]
+    }
+
+    /// Resets the count of completed fades and pending actions of the given
+    /// channel
+    pub(super) fn reset(speed_mode: ledc_mode_t, channel: ledc_channel_t) {
+        let state = state(speed_mode, channel);
+        state.done.store(false, Ordering::SeqCst);
+        state.completed.store(0, Ordering::SeqCst);
+        state.stop.store(false, Ordering::SeqCst);
+        state.hold.store(false, Ordering::SeqCst);
+        state.timer.store(ptr::null_mut(), Ordering::SeqCst);
+    }
+
+    /// Registers the fade end callback for the given channel and resets its
+    /// count of completed fades
+    pub(super) fn register(
+        speed_mode: ledc_mode_t,
+        channel: ledc_channel_t,
+    ) -> Result<(), EspError> {
+        reset(speed_mode, channel);
+        let state = state(speed_mode, channel);
+
+        let mut callbacks = ledc_cbs_t {
+            fade_cb: Some(callback),
+        };
+
+        // SAFETY: The callbacks get copied by the SDK and the state is static.
+        esp!(unsafe {
+            ledc_cb_register(
+                speed_mode,
+                channel,
+                &mut callbacks,
+                state as *const State as *mut c_types::c_void,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Returns the number of fades completed since registering the callback
+    pub(super) fn completed(speed_mode: ledc_mode_t, channel: ledc_channel_t) -> u32 {
+        state(speed_mode, channel).completed.load(Ordering::SeqCst)
+    }
+
+    /// Makes the callback stop the output of the given channel at
+    /// `idle_level` right at the end of the next fade
+    pub(super) fn stop_at_end(speed_mode: ledc_mode_t, channel: ledc_channel_t, idle_level: u32) {
+        let state = state(speed_mode, channel);
+        state.idle_level.store(idle_level, Ordering::SeqCst);
+        state.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Cancels stopping the output requested with [`stop_at_end()`]
+    pub(super) fn cancel_stop(speed_mode: ledc_mode_t, channel: ledc_channel_t) {
+        state(speed_mode, channel)
+            .stop
+            .store(false, Ordering::SeqCst);
+    }
+
+    /// Makes the callback set the duty of the given channel to `duty` right
+    /// at the end of the next fade
+    pub(super) fn hold_at_end(speed_mode: ledc_mode_t, channel: ledc_channel_t, duty: u32) {
+        let state = state(speed_mode, channel);
+        state.hold_duty.store(duty, Ordering::SeqCst);
+        state.hold.store(true, Ordering::SeqCst);
+    }
+
+    /// Cancels setting the duty requested with [`hold_at_end()`]
+    pub(super) fn cancel_hold(speed_mode: ledc_mode_t, channel: ledc_channel_t) {
+        state(speed_mode, channel)
+            .hold
+            .store(false, Ordering::SeqCst);
+    }
+
+    /// Makes the callback start the one-shot `esp_timer` `timer` right at the
+    /// end of the next fade of the given channel
+    pub(super) fn start_timer_at_end(
+        speed_mode: ledc_mode_t,
+        channel: ledc_channel_t,
+        timer: esp_timer_handle_t,
+    ) {
+        state(speed_mode, channel)
+            .timer
+            .store(timer, Ordering::SeqCst);
+    }
+
+    /// Cancels starting the timer requested with [`start_timer_at_end()`]
+    /// and stops `timer`
+    ///
+    /// This waits for the callback if it is just about to start the timer.
+    /// So the timer is not running afterwards, even if the fade has ended
+    /// meanwhile.
+    pub(super) fn cancel_timer(
+        speed_mode: ledc_mode_t,
+        channel: ledc_channel_t,
+        timer: esp_timer_handle_t,
+    ) {
+        let state = state(speed_mode, channel);
+        state.timer.store(ptr::null_mut(), Ordering::SeqCst);
+        // The callback flags starting before taking the timer, so it either
+        // finds none or is done once the flag is cleared. This spins for the
+        // duration of a call to esp_timer_start_once at most.
+        while state.starting.load(Ordering::SeqCst) {
+            core::hint::spin_loop();
+        }
+        // Fails if the timer is not running, which is fine.
+        unsafe { esp_timer_stop(timer) };
+    }
+
+    /// Returns a future completing with the end of the next fade of the given
+    /// channel
+    #[cfg(feature = "experimental")]
+    pub(super) fn next(speed_mode: ledc_mode_t, channel: ledc_channel_t) -> FadeEnd {
+        let state = state(speed_mode, channel);
+        state.done.store(false, Ordering::SeqCst);
+
+        FadeEnd { state }
+    }
+
+    unsafe extern "C" fn callback(
+        param: *const ledc_cb_param_t,
+        user_arg: *mut c_types::c_void,
+    ) -> bool {
+        if (*param).event == ledc_cb_event_t_LEDC_FADE_END_EVT {
+            let state = &*(user_arg as *const State);
+
+            // ledc_set_duty takes the fade semaphore of the driver and must not
+            // be called from ISR context. ledc_set_fade without any steps
+            // writes the duty guarded only by the driver's spinlock.
+            let halted = super::channel_halted((*param).speed_mode, (*param).channel);
+            if state.hold.swap(false, Ordering::SeqCst) && !halted {
+                ledc_set_fade(
+                    (*param).speed_mode,
+                    (*param).channel,
+                    state.hold_duty.load(Ordering::SeqCst),
+                    ledc_duty_direction_t_LEDC_DUTY_DIR_INCREASE,
+                    1,
+                    1,
+                    0,
+                );
+                ledc_update_duty((*param).speed_mode, (*param).channel);
+            }
+
+            // Stop first, before the next period of the channel starts.
+            if state.stop.swap(false, Ordering::SeqCst) {
+                ledc_stop(
+                    (*param).speed_mode,
+                    (*param).channel,
+                    state.idle_level.load(Ordering::SeqCst),
+                );
+            }
+
+            state.completed.fetch_add(1, Ordering::SeqCst);
+            state.done.store(true, Ordering::SeqCst);
+
+            // Starting fades is not allowed from ISR context, so leave this to
+            // the timer task.
+            state.starting.store(true, Ordering::SeqCst);
+            let timer = state.timer.swap(ptr::null_mut(), Ordering::SeqCst);
+            if !timer.is_null() {
+                esp_timer_start_once(timer, 1);
+            }
+            state.starting.store(false, Ordering::SeqCst);
+
+            // Take the waker out of the critical section before waking it.
+            #[cfg(feature = "experimental")]
+            {
+                let waker = state.waker.lock().take();
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Future completing with the end of a hardware fade
+    #[cfg(feature = "experimental")]
+    pub(super) struct FadeEnd {
+        state: &'static State,
+    }
+
+    #[cfg(feature = "experimental")]
+    impl Future for FadeEnd {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.state.done.load(Ordering::SeqCst) {
+                return Poll::Ready(());
+            }
+
+            *self.state.waker.lock() = Some(cx.waker().clone());
+
+            // The fade might have ended before the waker has been stored.
+            if self.state.done.load(Ordering::SeqCst) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Software dithering of the duty cycle between two adjacent values
+mod dither {
+    use core::ptr;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use core::time::Duration;
+
+    use esp_idf_sys::*;
+
+    const CHANNELS: usize = ledc_channel_t_LEDC_CHANNEL_MAX as usize;
+    const SPEED_MODES: usize = ledc_mode_t_LEDC_SPEED_MODE_MAX as usize;
+
+    /// Shortest interval between two dithering steps. The `esp_timer` can't
+    /// reliably dispatch callbacks much faster than this.
+    const MIN_INTERVAL: Duration = Duration::from_micros(100);
+
+    /// State shared between a channel and its dithering callback running in
+    /// the `esp_timer` task
+    struct State {
+        speed_mode: AtomicU32,
+        channel: AtomicU32,
+        hpoint: AtomicU32,
+        duty: AtomicU32,
+        fraction: AtomicU32,
+        depth: AtomicU32,
+        accumulator: AtomicU32,
+    }
+
+    impl State {
+        const fn new() -> Self {
+            Self {
+                speed_mode: AtomicU32::new(0),
+                channel: AtomicU32::new(0),
+                hpoint: AtomicU32::new(0),
+                duty: AtomicU32::new(0),
+                fraction: AtomicU32::new(0),
+                depth: AtomicU32::new(0),
+                accumulator: AtomicU32::new(0),
+            }
+        }
+    }
+
+    const NEW_STATE: State = State::new();
+    const NEW_STATES: [State; CHANNELS] = [NEW_STATE; CHANNELS];
+
+    static STATES: [[State; CHANNELS]; SPEED_MODES] = [NEW_STATES; SPEED_MODES];
+
+    /// Periodic `esp_timer` alternating the duty of a channel between two
+    /// adjacent values
+    pub(super) struct Dither {
+        handle: esp_timer_handle_t,
+        state: &'static State,
+        interval: Duration,
+        running: bool,
+    }
+
+    // SAFETY: The esp_timer API is thread-safe and the shared state consists
+    // of atomics only.
+    unsafe impl Send for Dither {}
+
+    impl Dither {
+        /// Creates dithering for the given channel with `depth` fractional
+        /// bits, stepping once per `period` of the PWM signal (but not faster
+        /// than `MIN_INTERVAL`)
+        pub(super) fn new(
+            speed_mode: ledc_mode_t,
+            channel: ledc_channel_t,
+            depth: u8,
+            period: Duration,
+        ) -> Result<Self, EspError> {
+            let state = &STATES[speed_mode as usize][channel as usize];
+            state.speed_mode.store(speed_mode as u32, Ordering::SeqCst);
+            state.channel.store(channel as u32, Ordering::SeqCst);
+            state.depth.store(depth as u32, Ordering::SeqCst);
+            state.fraction.store(0, Ordering::SeqCst);
+            state.accumulator.store(0, Ordering::SeqCst);
+
+            let args = esp_timer_create_args_t {
+                callback: Some(callback),
+                arg: state as *const State as *mut c_types::c_void,
+                dispatch_method: esp_timer_dispatch_t_ESP_TIMER_TASK,
+                name: b"ledc_dither\0".as_ptr() as *const _,
+                ..Default::default()
+            };
+
+            let mut handle: esp_timer_handle_t = ptr::null_mut();
+            esp!(unsafe { esp_timer_create(&args, &mut handle) })?;
+
+            Ok(Self {
+                handle,
+                state,
+                interval: period.max(MIN_INTERVAL),
+                running: false,
+            })
+        }
+
+        /// Returns the number of fractional bits
+        pub(super) fn depth(&self) -> u8 {
+            self.state.depth.load(Ordering::SeqCst) as u8
+        }
+
+        /// Changes the number of fractional bits. The fraction gets reset.
+        pub(super) fn set_depth(&mut self, depth: u8) {
+            self.state.fraction.store(0, Ordering::SeqCst);
+            self.state.accumulator.store(0, Ordering::SeqCst);
+            self.state.depth.store(depth as u32, Ordering::SeqCst);
+        }
+
+        /// Sets the duty to alternate from, the fraction of steps where
+        /// `duty + 1` gets output instead and starts dithering if it is not
+        /// running yet
+        pub(super) fn set(
+            &mut self,
+            duty: u32,
+            fraction: u32,
+            hpoint: u32,
+        ) -> Result<(), EspError> {
+            self.state.hpoint.store(hpoint, Ordering::SeqCst);
+            self.state.duty.store(duty, Ordering::SeqCst);
+            self.state.fraction.store(fraction, Ordering::SeqCst);
+
+            if !self.running {
+                esp!(unsafe {
+                    esp_timer_start_periodic(self.handle, self.interval.as_micros() as u64)
+                })?;
+                self.running = true;
+            }
+
+            Ok(())
+        }
+
+        /// Halts dithering until the next call to [`set()`](Dither::set())
+        pub(super) fn stop(&mut self) -> Result<(), EspError> {
+            if self.running {
+                esp!(unsafe { esp_timer_stop(self.handle) })?;
+                self.running = false;
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Dither {
+        fn drop(&mut self) {
+            let _ = self.stop();
+            unsafe { esp_timer_delete(self.handle) };
+        }
+    }
+
+    unsafe extern "C" fn callback(arg: *mut c_types::c_void) {
+        let state = &*(arg as *const State);
+
+        let fraction = state.fraction.load(Ordering::SeqCst);
+        let speed_mode = state.speed_mode.load(Ordering::SeqCst) as ledc_mode_t;
+        let channel = state.channel.load(Ordering::SeqCst) as ledc_channel_t;
+        // Updating the duty would restart the output of a halted channel.
+        if fraction == 0 || super::channel_halted(speed_mode, channel) {
+            return;
+        }
+
+        // First-order error diffusion: output the higher duty whenever the
+        // accumulated fraction overflows a full step.
+        let steps = 1 << state.depth.load(Ordering::SeqCst);
+        let mut accumulator = state.accumulator.load(Ordering::SeqCst) + fraction;
+        let mut duty = state.duty.load(Ordering::SeqCst);
+        if accumulator >= steps {
+            accumulator -= steps;
+            duty += 1;
+        }
+        state.accumulator.store(accumulator, Ordering::SeqCst);
+
+        ledc_set_duty_and_update(
+            speed_mode,
+            channel,
+            duty,
+            state.hpoint.load(Ordering::SeqCst),
+        );
+    }
+}
+
+/// Maximum number of pulses emitted by a single call of
+/// [`Channel::pulse_n()`], limited by the width of the hardware fade cycle
+/// counter
+#[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+pub const MAX_PULSE_COUNT: u32 = 1023;
+
+/// Errors reported by the LED Control peripheral abstraction
+#[derive(Debug, Copy, Clone)]
+pub enum LedcError {
+    /// The requested frequency can't be generated at the requested
+    /// resolution from the selected clock source. `max_frequency` is the
+    /// highest frequency achievable with this combination.
+    FrequencyTooHighForResolution { max_frequency: Hertz },
+    /// The requested frequency is too low for the clock divider at the
+    /// requested resolution from the selected clock source. `min_frequency`
+    /// is the lowest frequency achievable with this combination.
+    FrequencyTooLowForResolution { min_frequency: Hertz },
+    /// The SDK rejected the timer configuration
+    TimerConfig(EspError),
+    /// The SDK rejected the channel configuration, e.g. for an unsuitable
+    /// GPIO
+    ChannelConfig(EspError),
+    /// Installing the fade function failed
+    FadeFuncInstall(EspError),
+    /// The hardware timer is already configured by another [`Timer`]
+    TimerInUse,
+    /// More distinct frequencies have been requested from a
+    /// [`MultiTimer`](multi::MultiTimer) than hardware timers are available
+    OutOfTimers,
+    /// No timer of a [`MultiTimer`](multi::MultiTimer) has been configured
+    /// for the requested frequency
+    UnknownFrequency,
+    /// Any other error reported by the SDK
+    Esp(EspError),
+}
+
+impl From<EspError> for LedcError {
+    fn from(e: EspError) -> Self {
+        LedcError::Esp(e)
+    }
+}
+
+impl core::fmt::Display for LedcError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LedcError::FrequencyTooHighForResolution { max_frequency } => write!(
+                f,
+                "frequency too high for resolution (max. {})",
+                max_frequency
+            ),
+            LedcError::FrequencyTooLowForResolution { min_frequency } => write!(
+                f,
+                "frequency too low for resolution (min. {})",
+                min_frequency
+            ),
+            LedcError::TimerConfig(e) => write!(f, "timer configuration failed: {}", e),
+            LedcError::ChannelConfig(e) => write!(f, "channel configuration failed: {}", e),
+            LedcError::FadeFuncInstall(e) => write!(f, "installing fade function failed: {}", e),
+            LedcError::TimerInUse => write!(f, "timer already in use"),
+            LedcError::OutOfTimers => write!(f, "out of timers"),
+            LedcError::UnknownFrequency => write!(f, "no timer configured for frequency"),
+            LedcError::Esp(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LedcError {}
+
+/// Position within the timer period where the high phase of a channel
+/// begins
+///
+/// The SDK takes the hpoint as `int` for configuring a channel but as
+/// `uint32_t` for updating its duty. An hpoint is validated to lie within the
+/// timer period, which has at most 2^20 counts, and thus converts losslessly
+/// to both.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Hpoint(u32);
+
+impl Hpoint {
+    /// Creates an hpoint for a timer with the given resolution
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` if `value` lies outside the timer
+    /// period, i.e. is larger than the maximum duty.
+    pub fn new(value: u32, resolution: config::Resolution) -> Result<Self, EspError> {
+        if value > resolution.max_duty() {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        Ok(Self(value))
+    }
+
+    /// Returns the hpoint in timer counts
+    pub fn get(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the hpoint for `ledc_channel_config_t`
+    fn for_config(self) -> i32 {
+        self.0 as i32
+    }
+}
+
+/// Whether starting a hardware fade waits for its end
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum FadeMode {
+    /// Returns immediately after starting the fade
+    NoWait,
+    /// Blocks the calling task until the fade is done
+    WaitDone,
+}
+
+impl From<FadeMode> for ledc_fade_mode_t {
+    fn from(mode: FadeMode) -> Self {
+        match mode {
+            FadeMode::NoWait => ledc_fade_mode_t_LEDC_FADE_NO_WAIT,
+            FadeMode::WaitDone => ledc_fade_mode_t_LEDC_FADE_WAIT_DONE,
+        }
+    }
+}
+
+/// Interpolation between the start and end value of a sweep
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Interpolation {
+    /// Changes the value by the same amount with each step
+    Linear,
+    /// Changes the value by the same ratio with each step, which sounds
+    /// evenly paced for tones
+    #[cfg(feature = "std")]
+    Logarithmic,
+}
+
+/// Returns the highest frequency achievable with `resolution` from `source`
+fn max_frequency(source: config::ClockSource, resolution: config::Resolution) -> Hertz {
+    // The timer counts at the source clock divided by a divider of at least
+    // one and one period takes 2^resolution counts.
+    source.frequency() / (1u32 << resolution.bits())
+}
+
+/// Largest value of the clock divider, which is a fixed point number with
+/// 10 integer and 8 fractional bits
+const MAX_DIVIDER: u64 = 0x3ffff;
+
+/// Returns the lowest frequency achievable with `resolution` from `source`
+fn min_frequency(source: config::ClockSource, resolution: config::Resolution) -> Hertz {
+    // One period takes 2^resolution counts at the source clock divided by the
+    // largest divider. Round up, as the frequency below is not achievable.
+    let source: u32 = source.slowest_frequency().into();
+    let counts = MAX_DIVIDER << resolution.bits();
+    let frequency = (source as u64 * 256 + counts - 1) / counts;
+
+    (frequency as u32).Hz()
+}
+
+/// Converts a fade duration to the milliseconds expected by the SDK, rounding
+/// up to at least 1 ms and saturating at the largest representable value
+fn fade_millis(duration: Duration) -> i32 {
+    let millis = (duration.as_nanos() + 999_999) / 1_000_000;
+    millis.clamp(1, i32::MAX as u128) as i32
+}
+
+/// Clamps a fraction to `0.0..=1.0` and maps NaN to `0.0`
+fn clamp_fraction(fraction: f32) -> f32 {
+    if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    }
+}
+
+/// Scales a duty or hpoint from `from` to `to` keeping its ratio to the
+/// period, rounded to the nearest count
+fn rescale_duty(value: Duty, from: config::Resolution, to: config::Resolution) -> Duty {
+    let from_counts = from.max_duty() as u64 + 1;
+    let to_counts = to.max_duty() as u64 + 1;
+    let scaled = (value as u64 * to_counts + from_counts / 2) / from_counts;
+
+    scaled.min(to.max_duty() as u64) as Duty
+}
+
+/// Types for configuring the LED Control peripheral
+pub mod config {
+    use super::*;
+    use crate::units::*;
+
+    pub use super::chip::{InvalidResolutionError, Resolution};
+    pub use crate::gpio::Level;
+
+    /// Clock source of a LED Control timer
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum ClockSource {
+        /// Selects a source clock depending on frequency and resolution
+        Auto,
+        /// APB clock (80 MHz)
+        ApbClk,
+        /// RC fast clock (about 8 MHz), which keeps running in light sleep.
+        /// On the ESP32 it can only be used in low speed mode.
+        RcFastClk,
+        /// Reference tick (1 MHz)
+        ///
+        /// Other than the APB clock, the reference tick keeps its frequency
+        /// when dynamic frequency scaling lowers the APB clock together with
+        /// the CPU clock. This gives stable timing for servos, see
+        /// [`servo::timer_config()`](super::servo::timer_config()), at the
+        /// cost of resolution: a 50 Hz period has only 20000 reference ticks,
+        /// which allows a resolution of at most 14 bits.
+        #[cfg(any(esp32, esp32s2))]
+        RefTick,
+    }
+
+    impl ClockSource {
+        /// Returns the (highest) frequency of this clock source. For automatic
+        /// selection, this is the frequency of the fastest candidate.
+        pub(crate) const fn frequency(&self) -> Hertz {
+            match self {
+                ClockSource::Auto | ClockSource::ApbClk => Hertz(80_000_000),
+                ClockSource::RcFastClk => Hertz(8_000_000),
+                #[cfg(any(esp32, esp32s2))]
+                ClockSource::RefTick => Hertz(1_000_000),
+            }
+        }
+
+        /// Returns the (lowest) frequency of this clock source. For automatic
+        /// selection, this is the frequency of the slowest candidate.
+        pub(crate) const fn slowest_frequency(&self) -> Hertz {
+            match self {
+                #[cfg(any(esp32, esp32s2))]
+                ClockSource::Auto => Hertz(1_000_000),
+                #[cfg(not(any(esp32, esp32s2)))]
+                ClockSource::Auto => Hertz(8_000_000),
+                _ => self.frequency(),
+            }
+        }
+    }
+
+    impl From<ClockSource> for ledc_clk_cfg_t {
+        fn from(source: ClockSource) -> Self {
+            match source {
+                ClockSource::Auto => ledc_clk_cfg_t_LEDC_AUTO_CLK,
+                ClockSource::ApbClk => ledc_clk_cfg_t_LEDC_USE_APB_CLK,
+                ClockSource::RcFastClk => ledc_clk_cfg_t_LEDC_USE_RTC8M_CLK,
+                #[cfg(any(esp32, esp32s2))]
+                ClockSource::RefTick => ledc_clk_cfg_t_LEDC_USE_REF_TICK,
+            }
+        }
+    }
+
+    /// Speed mode of a LED Control timer and its channels as reported at
+    /// runtime
+    ///
+    /// The speed mode gets selected with the type parameter of [`Timer`], see
+    /// [`LowSpeed`] and [`HighSpeed`]. High speed mode is only available on the
+    /// ESP32.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum SpeedMode {
+        LowSpeed,
+        /// Updates duty and frequency immediately by hardware instead of at
+        /// the next overflow of the timer
+        #[cfg(esp32)]
+        HighSpeed,
+    }
+
+    impl From<SpeedMode> for ledc_mode_t {
+        fn from(mode: SpeedMode) -> Self {
+            match mode {
+                SpeedMode::LowSpeed => ledc_mode_t_LEDC_LOW_SPEED_MODE,
+                #[cfg(esp32)]
+                SpeedMode::HighSpeed => ledc_mode_t_LEDC_HIGH_SPEED_MODE,
+            }
+        }
+    }
+
+    /// Frequency and resolution of a timer which are known to be achievable
+    /// together from a clock source
+    ///
+    /// Other than setting them individually in [`TimerConfig`], an invalid
+    /// combination gets rejected when creating the timing already.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct Timing {
+        frequency: Hertz,
+        resolution: Resolution,
+        clock_source: ClockSource,
+    }
+
+    impl Timing {
+        /// Creates a timing for `frequency` at `resolution` from
+        /// `clock_source`
+        ///
+        /// Returns [`LedcError::FrequencyTooHighForResolution`] if the
+        /// clock source is too slow for this combination and
+        /// [`LedcError::FrequencyTooLowForResolution`] if it is too fast for
+        /// the clock divider.
+        pub fn new(
+            frequency: Hertz,
+            resolution: Resolution,
+            clock_source: ClockSource,
+        ) -> Result<Self, LedcError> {
+            let max_frequency = max_frequency(clock_source, resolution);
+            if frequency > max_frequency {
+                return Err(LedcError::FrequencyTooHighForResolution { max_frequency });
+            }
+            let min_frequency = min_frequency(clock_source, resolution);
+            if frequency < min_frequency {
+                return Err(LedcError::FrequencyTooLowForResolution { min_frequency });
+            }
+
+            Ok(Self {
+                frequency,
+                resolution,
+                clock_source,
+            })
+        }
+
+        /// Creates a timing for `frequency` at the highest resolution
+        /// achievable from `clock_source`
+        ///
+        /// Returns [`LedcError::FrequencyTooHighForResolution`] if not even a
+        /// 1 bit resolution is possible and
+        /// [`LedcError::FrequencyTooLowForResolution`] if the frequency is too
+        /// low even for the highest resolution.
+        pub fn with_highest_resolution(
+            frequency: Hertz,
+            clock_source: ClockSource,
+        ) -> Result<Self, LedcError> {
+            let source_frequency = clock_source.frequency();
+            let resolution = Resolution::highest_for(frequency.into(), source_frequency.into())
+                .ok_or(LedcError::FrequencyTooHighForResolution {
+                    max_frequency: source_frequency / 2,
+                })?;
+            // Higher resolutions allow lower frequencies, so there is no
+            // other candidate if this one fails.
+            let min_frequency = min_frequency(clock_source, resolution);
+            if frequency < min_frequency {
+                return Err(LedcError::FrequencyTooLowForResolution { min_frequency });
+            }
+
+            Ok(Self {
+                frequency,
+                resolution,
+                clock_source,
+            })
+        }
+
+        pub fn frequency(&self) -> Hertz {
+            self.frequency
+        }
+
+        pub fn resolution(&self) -> Resolution {
+            self.resolution
+        }
+
+        pub fn clock_source(&self) -> ClockSource {
+            self.clock_source
+        }
+    }
+
+    #[derive(Copy, Clone, Debug)]
+    pub struct TimerConfig {
+        pub frequency: Hertz,
+        pub resolution: Resolution,
+        pub clock_source: ClockSource,
+        /// Use the highest resolution achievable at the configured frequency
+        /// instead of `resolution`
+        pub auto_resolution: bool,
+        /// Keep the timer running in light sleep, see
+        /// [`keep_in_sleep()`](TimerConfig::keep_in_sleep())
+        pub keep_in_sleep: bool,
+    }
+
+    impl TimerConfig {
+        #[must_use]
+        pub fn frequency(mut self, frequency: Hertz) -> Self {
+            self.frequency = frequency;
+            self
+        }
+
+        #[must_use]
+        pub fn resolution(mut self, resolution: Resolution) -> Self {
+            self.resolution = resolution;
+            self
+        }
+
+        #[must_use]
+        pub fn clock_source(mut self, source: ClockSource) -> Self {
+            self.clock_source = source;
+            self
+        }
+
+        /// Returns the number of bits of the configured resolution
+        pub fn resolution_bits(&self) -> u8 {
+            self.resolution.bits()
+        }
+
+        /// Sets frequency, resolution and clock source from a validated
+        /// timing and disables `auto_resolution`
+        #[must_use]
+        pub fn timing(mut self, timing: Timing) -> Self {
+            self.frequency = timing.frequency;
+            self.resolution = timing.resolution;
+            self.clock_source = timing.clock_source;
+            self.auto_resolution = false;
+            self
+        }
+
+        #[must_use]
+        pub fn auto_resolution(mut self, auto: bool) -> Self {
+            self.auto_resolution = auto;
+            self
+        }
+
+        /// Keeps the timer and thus the PWM outputs of its channels running
+        /// while the chip is in light sleep
+        ///
+        /// This selects [`ClockSource::RcFastClk`] instead of the configured
+        /// clock source, as the APB clock stops in light sleep, and keeps the
+        /// RC fast clock powered during sleep as long as such a timer exists.
+        /// The frequency has to be achievable from the about 8 MHz of this
+        /// clock. On the ESP32 this is only supported in low speed mode. The
+        /// ESP32-S2, ESP32-S3 and ESP32-C3 support it for all their timers.
+        #[must_use]
+        pub fn keep_in_sleep(mut self, keep: bool) -> Self {
+            self.keep_in_sleep = keep;
+            self
+        }
+    }
+
+    impl Default for TimerConfig {
+        fn default() -> Self {
+            TimerConfig {
+                frequency: 1000.Hz(),
+                resolution: Resolution::Bits8,
+                clock_source: ClockSource::Auto,
+                auto_resolution: false,
+                keep_in_sleep: false,
+            }
+        }
+    }
+
+    /// Interrupt generated by a LED Control output channel
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum InterruptType {
+        Disabled,
+        /// Interrupt at the end of a hardware fade
+        FadeEnd,
+    }
+
+    impl From<InterruptType> for ledc_intr_type_t {
+        fn from(interrupt: InterruptType) -> Self {
+            match interrupt {
+                InterruptType::Disabled => ledc_intr_type_t_LEDC_INTR_DISABLE,
+                InterruptType::FadeEnd => ledc_intr_type_t_LEDC_INTR_FADE_END,
+            }
+        }
+    }
+
+    /// Configuration of a LED Control output channel
+    ///
+    /// [`Channel::new()`](super::Channel::new()) uses the default
+    /// configuration.
+    #[derive(Copy, Clone, Debug)]
+    pub struct ChannelConfig {
+        /// Output level of the pin while the channel is disabled, stopped or
+        /// dropped
+        pub idle_level: Level,
+        /// Inverts the output signal in hardware. Duty values keep referring
+        /// to the non-inverted signal.
+        pub invert: bool,
+        /// Position within the timer period where the high phase begins
+        pub hpoint: u32,
+        pub interrupt_type: InterruptType,
+        /// Exponent of the gamma curve applied by
+        /// [`Channel::set_brightness()`](super::Channel::set_brightness())
+        pub gamma: f32,
+        /// Reads back the duty from the hardware after setting it and reports
+        /// a mismatch as an error, see
+        /// [`Channel::set_verify()`](super::Channel::set_verify())
+        pub verify: bool,
+        /// Installs the fade function of the SDK when creating the channel,
+        /// see [`install_fade()`](ChannelConfig::install_fade())
+        pub install_fade: bool,
+        /// Duty the output starts with, see
+        /// [`initial_duty()`](ChannelConfig::initial_duty())
+        pub initial_duty: Duty,
+    }
+
+    impl ChannelConfig {
+        #[must_use]
+        pub fn idle_level(mut self, level: Level) -> Self {
+            self.idle_level = level;
+            self
+        }
+
+        #[must_use]
+        pub fn invert(mut self, invert: bool) -> Self {
+            self.invert = invert;
+            self
+        }
+
+        #[must_use]
+        pub fn hpoint(mut self, hpoint: u32) -> Self {
+            self.hpoint = hpoint;
+            self
+        }
+
+        #[must_use]
+        pub fn interrupt_type(mut self, interrupt: InterruptType) -> Self {
+            self.interrupt_type = interrupt;
+            self
+        }
+
+        #[must_use]
+        pub fn gamma(mut self, gamma: f32) -> Self {
+            self.gamma = gamma;
+            self
+        }
+
+        #[must_use]
+        pub fn verify(mut self, verify: bool) -> Self {
+            self.verify = verify;
+            self
+        }
+
+        /// Selects whether the fade function of the SDK gets installed when
+        /// creating the channel (the default)
+        ///
+        /// The fade function takes some RAM and an ISR, which is wasted for
+        /// channels only ever setting their duty directly. Without installing
+        /// it up front, the channel installs it on the first hardware fade or
+        /// when enabling dithering instead.
+        #[must_use]
+        pub fn install_fade(mut self, install: bool) -> Self {
+            self.install_fade = install;
+            self
+        }
+
+        /// Sets the duty the output starts with instead of zero
+        ///
+        /// The duty is part of the channel configuration passed to the SDK,
+        /// so the output starts at this level right away, e.g. without a dark
+        /// flash of a backlight at boot. Creating the channel fails with
+        /// `ESP_ERR_INVALID_ARG` if the duty exceeds the maximum duty of the
+        /// timer or the high phase starting at the hpoint exceeds the period.
+        #[must_use]
+        pub fn initial_duty(mut self, duty: Duty) -> Self {
+            self.initial_duty = duty;
+            self
+        }
+    }
+
+    impl Default for ChannelConfig {
+        fn default() -> Self {
+            ChannelConfig {
+                idle_level: Level::Low,
+                invert: false,
+                hpoint: HPOINT,
+                interrupt_type: InterruptType::Disabled,
+                gamma: 2.2,
+                verify: false,
+                install_fade: true,
+                initial_duty: 0,
+            }
+        }
+    }
+}
+
+/// LED Control timer abstraction
+///
+/// The speed mode is a type parameter, so channels can only be created on
+/// and bound to timers of the same speed mode. It defaults to [`LowSpeed`],
+/// which is available on all chips. The ESP32 additionally supports
+/// [`HighSpeed`] timers created with [`Timer::with_speed_mode()`].
+pub struct Timer<T: HwTimer, S: HwSpeedMode = LowSpeed> {
+    instance: T,
+    speed_mode: ledc_mode_t,
+    _speed_mode: PhantomData<S>,
+    resolution: config::Resolution,
+    clock_source: config::ClockSource,
+    /// Frequency in hertz last requested, see
+    /// [`Timer::requested_vs_actual()`]
+    requested: AtomicU32,
+}
+
+// SAFETY: All methods taking a shared reference only read plain data or call
+// into the LEDC driver of the SDK which guards its register accesses with a
+// spinlock. This allows sharing a timer between channels living in different
+// tasks and thus makes them Send.
+unsafe impl<T: HwTimer, S: HwSpeedMode> Sync for Timer<T, S> {}
+
+impl<T: HwTimer> Timer<T> {
+    /// Creates a new LED Control timer abstraction in low speed mode
+    ///
+    /// Returns [`LedcError::FrequencyTooHighForResolution`] or
+    /// [`LedcError::FrequencyTooLowForResolution`] if the frequency can't be
+    /// generated at the configured resolution from the configured clock
+    /// source and [`LedcError::TimerInUse`] if this hardware timer has
+    /// already been configured by another `Timer` which has not been released
+    /// or dropped yet.
+    pub fn new(instance: T, config: &config::TimerConfig) -> Result<Self, LedcError> {
+        Self::with_speed_mode(instance, LowSpeed, config)
+    }
+
+    /// Creates a new LED Control timer abstraction in low speed mode like
+    /// [`new()`](Timer::new()) but hands back the instance on errors
+    pub fn try_new(instance: T, config: &config::TimerConfig) -> Result<Self, TimerSetupError<T>> {
+        Self::try_with_speed_mode(instance, LowSpeed, config)
+    }
+}
+
+impl<T: HwTimer, S: HwSpeedMode> Timer<T, S> {
+    /// Creates a new LED Control timer abstraction in the speed mode `S`,
+    /// e.g. `Timer::with_speed_mode(timer, HighSpeed, &config)` on the ESP32
+    ///
+    /// Errors are reported as by [`new()`](Timer::new()).
+    pub fn with_speed_mode(
+        instance: T,
+        speed_mode: S,
+        config: &config::TimerConfig,
+    ) -> Result<Self, LedcError> {
+        Self::try_with_speed_mode(instance, speed_mode, config).map_err(|err| err.error)
+    }
+
+    /// Creates a new LED Control timer abstraction in the speed mode `S` like
+    /// [`with_speed_mode()`](Timer::with_speed_mode()) but hands back the
+    /// instance on errors
+    ///
+    /// The hardware timer is left unconfigured when this fails, so the
+    /// instance can be used for another attempt, e.g. at a different
+    /// frequency.
+    pub fn try_with_speed_mode(
+        instance: T,
+        _speed_mode: S,
+        config: &config::TimerConfig,
+    ) -> Result<Self, TimerSetupError<T>> {
+        match Self::configure(config) {
+            Ok((resolution, clock_source)) => Ok(Timer {
+                instance,
+                speed_mode: S::speed_mode(),
+                _speed_mode: PhantomData,
+                resolution,
+                clock_source,
+                requested: AtomicU32::new(config.frequency.into()),
+            }),
+            Err(error) => Err(TimerSetupError { error, instance }),
+        }
+    }
+
+    /// Configures and registers the hardware timer and returns its resolution
+    /// and clock source
+    ///
+    /// Nothing is left behind on errors, i.e. the timer is unregistered
+    /// again.
+    fn configure(
+        config: &config::TimerConfig,
+    ) -> Result<(config::Resolution, config::ClockSource), LedcError> {
+        let speed_mode = S::speed_mode();
+
+        #[cfg(esp32)]
+        if config.keep_in_sleep && speed_mode == ledc_mode_t_LEDC_HIGH_SPEED_MODE {
+            return Err(LedcError::TimerConfig(
+                EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap(),
+            ));
+        }
+
+        let clock_source = if config.keep_in_sleep {
+            config::ClockSource::RcFastClk
+        } else {
+            config.clock_source
+        };
+
+        let timing = if config.auto_resolution {
+            config::Timing::with_highest_resolution(config.frequency, clock_source)?
+        } else {
+            config::Timing::new(config.frequency, config.resolution, clock_source)?
+        };
+        let resolution = timing.resolution();
+
+        let bit = timer_bit(speed_mode, T::timer());
+        if CONFIGURED_TIMERS.fetch_or(bit, Ordering::SeqCst) & bit != 0 {
+            return Err(LedcError::TimerInUse);
+        }
+
+        let timer_config = ledc_timer_config_t {
+            speed_mode,
+            timer_num: T::timer(),
+            __bindgen_anon_1: ledc_timer_config_t__bindgen_ty_1 {
+                duty_resolution: resolution.timer_bits(),
+            },
+            freq_hz: config.frequency.into(),
+            clk_cfg: clock_source.into(),
+        };
+
+        // SAFETY: We own the instance and therefor are safe to configure it.
+        // Configuring doesn't resume a timer paused before, e.g. by releasing
+        // it.
+        if let Err(err) = esp!(unsafe { ledc_timer_config(&timer_config) })
+            .and_then(|_| esp!(unsafe { ledc_timer_resume(speed_mode, T::timer()) }))
+        {
+            CONFIGURED_TIMERS.fetch_and(!bit, Ordering::SeqCst);
+            return Err(LedcError::TimerConfig(err));
+        }
+
+        if config.keep_in_sleep {
+            SLEEP_TIMERS.fetch_or(bit, Ordering::SeqCst);
+            if let Err(err) = esp!(unsafe {
+                esp_sleep_pd_config(
+                    esp_sleep_pd_domain_t_ESP_PD_DOMAIN_RTC8M,
+                    esp_sleep_pd_option_t_ESP_PD_OPTION_ON,
+                )
+            }) {
+                // Leave the timer stopped and unregistered, as the instance
+                // gets handed back with the error.
+                unsafe {
+                    ledc_timer_pause(speed_mode, T::timer());
+                    ledc_timer_rst(speed_mode, T::timer());
+                }
+                SLEEP_TIMERS.fetch_and(!bit, Ordering::SeqCst);
+                CONFIGURED_TIMERS.fetch_and(!bit, Ordering::SeqCst);
+                return Err(err.into());
+            }
+        }
+
+        Ok((resolution, clock_source))
+    }
+
+    /// Returns whether this hardware timer is currently configured by a
+    /// `Timer` in the speed mode `S`
+    pub fn is_configured() -> bool {
+        CONFIGURED_TIMERS.load(Ordering::SeqCst) & timer_bit(S::speed_mode(), T::timer()) != 0
+    }
+
+    /// Changes the frequency of the timer at runtime
+    ///
+    /// This takes a shared reference so that the frequency can be changed
+    /// while channels are borrowing this timer. An error is returned if the
+    /// frequency can't be achieved at the configured resolution.
+    ///
+    /// Only the clock divider changes while the resolution and thus the
+    /// number of counts per period stay the same. So the duty of the bound
+    /// channels keeps its ratio to the period and e.g. the brightness of a
+    /// dimmed LED doesn't jump when changing the frequency.
+    pub fn set_frequency(&self, frequency: Hertz) -> Result<(), EspError> {
+        esp!(unsafe { ledc_set_freq(self.speed_mode, T::timer(), frequency.into()) })?;
+        self.requested.store(frequency.into(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the actual frequency of the timer
+    ///
+    /// The clock divider quantizes the frequency and therefore it usually
+    /// differs slightly from the requested one.
+    pub fn get_frequency(&self) -> Result<Hertz, EspError> {
+        let frequency = unsafe { ledc_get_freq(self.speed_mode, T::timer()) };
+
+        // ledc_get_freq signals errors by returning zero.
+        if frequency == 0 {
+            Err(EspError::from(ESP_FAIL).unwrap())
+        } else {
+            Ok(frequency.Hz())
+        }
+    }
+
+    /// Returns the frequency last requested when creating the timer or
+    /// changing its frequency
+    pub fn requested_frequency(&self) -> Hertz {
+        self.requested.load(Ordering::Relaxed).Hz()
+    }
+
+    /// Returns the requested and the actual frequency of the timer
+    ///
+    /// Right after creating a timer, this tells how much the quantization by
+    /// the clock divider has shifted the frequency, e.g. for checking whether
+    /// the frequency error of a tone generator stays inaudible. See
+    /// [`frequency_error()`](Timer::frequency_error()) for the relative
+    /// deviation.
+    pub fn requested_vs_actual(&self) -> Result<(Hertz, Hertz), EspError> {
+        Ok((self.requested_frequency(), self.get_frequency()?))
+    }
+
+    /// Returns the deviation of the actual frequency from the requested one
+    /// relative to the requested frequency
+    ///
+    /// A value of `0.03` means that the timer runs 3 % too fast and `-0.03`
+    /// that it runs 3 % too slow. If this is too much, a lower resolution
+    /// allows for a finer grained clock divider.
+    pub fn frequency_error(&self) -> Result<f32, EspError> {
+        let (requested, actual) = self.requested_vs_actual()?;
+        let requested = u32::from(requested) as f32;
+
+        Ok((u32::from(actual) as f32 - requested) / requested)
+    }
+
+    /// Returns the actual period of the PWM signal generated by this timer
+    pub fn get_period(&self) -> Result<Duration, EspError> {
+        // get_frequency never returns zero.
+        Ok(self.get_frequency()?.period().unwrap())
+    }
+
+    /// Changes the period of the PWM signal generated by this timer
+    ///
+    /// The period gets converted to the closest frequency in hertz which is
+    /// set with [`set_frequency()`](Timer::set_frequency()). Returns
+    /// `ESP_ERR_INVALID_ARG` for periods which don't result in a frequency of
+    /// at least 1 Hz.
+    pub fn set_period(&self, period: Duration) -> Result<(), EspError> {
+        let frequency = Hertz::from_period(period)
+            .map_err(|_| EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap())?;
+        self.set_frequency(frequency)
+    }
+
+    /// Changes frequency and resolution of the timer together in a single
+    /// timer configuration
+    ///
+    /// Setting them one after another could pass through a combination the
+    /// clock source can't generate. Returns `ESP_ERR_INVALID_ARG` if
+    /// `frequency` can't be achieved at `resolution` and leaves the timer
+    /// untouched in this case.
+    ///
+    /// This takes an exclusive reference as channels cache the maximum duty
+    /// derived from the resolution, so no channel may borrow the timer
+    /// meanwhile. Hardware channels bound to it keep running with their raw
+    /// duty values. A channel owning its timer can be reconfigured with
+    /// [`Channel::reconfigure_timer()`] which rescales its duty as well.
+    pub fn reconfigure(
+        &mut self,
+        frequency: Hertz,
+        resolution: config::Resolution,
+    ) -> Result<(), EspError> {
+        config::Timing::new(frequency, resolution, self.clock_source)
+            .map_err(|_| EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap())?;
+
+        let timer_config = ledc_timer_config_t {
+            speed_mode: self.speed_mode,
+            timer_num: T::timer(),
+            __bindgen_anon_1: ledc_timer_config_t__bindgen_ty_1 {
+                duty_resolution: resolution.timer_bits(),
+            },
+            freq_hz: frequency.into(),
+            clk_cfg: self.clock_source.into(),
+        };
+
+        esp!(unsafe { ledc_timer_config(&timer_config) })?;
+        self.resolution = resolution;
+        self.requested.store(frequency.into(), Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Changes only the resolution of the timer and keeps its current
+    /// frequency
+    ///
+    /// This is a shortcut for [`reconfigure()`](Timer::reconfigure()) with
+    /// the actual frequency as returned by
+    /// [`get_frequency()`](Timer::get_frequency()), so the frequency may shift
+    /// slightly by getting quantized once more. Returns `ESP_ERR_INVALID_ARG`
+    /// if the current frequency can't be generated at `resolution`. Use
+    /// [`Channel::set_timer_resolution()`] for a channel owning its timer,
+    /// which rescales the duty of the channel as well.
+    pub fn set_resolution(&mut self, resolution: config::Resolution) -> Result<(), EspError> {
+        let frequency = self.get_frequency()?;
+        self.reconfigure(frequency, resolution)
+    }
+
+    /// Sweeps the frequency from `from` to `to` within `duration` in `steps`
+    /// steps, e.g. for generating a siren
+    ///
+    /// This blocks the calling task until the sweep is done. The time per step
+    /// gets rounded up to whole FreeRTOS ticks. So `duration` should be large
+    /// compared to `steps` ticks for achieving the requested timing. Returns
+    /// `ESP_ERR_INVALID_ARG` for zero steps.
+    pub fn sweep_frequency(
+        &self,
+        from: Hertz,
+        to: Hertz,
+        duration: Duration,
+        steps: u32,
+        interpolation: Interpolation,
+    ) -> Result<(), EspError> {
+        if steps == 0 {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        let from = u32::from(from) as f32;
+        let to = u32::from(to) as f32;
+        let delay = TickType::from(duration / steps).0;
+
+        for step in 0..=steps {
+            let position = step as f32 / steps as f32;
+            let frequency = match interpolation {
+                Interpolation::Linear => from + (to - from) * position,
+                #[cfg(feature = "std")]
+                Interpolation::Logarithmic => from * (to / from).powf(position),
+            };
+            self.set_frequency(((frequency + 0.5) as u32).Hz())?;
+
+            if step < steps {
+                unsafe { vTaskDelay(delay) };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pauses the timer and thus freezes the output of all channels bound to
+    /// it. Operation can be resumed with [`resume()`](Timer::resume()).
+    pub fn pause(&self) -> Result<(), EspError> {
+        esp!(unsafe { ledc_timer_pause(self.speed_mode, T::timer()) })?;
+        Ok(())
+    }
+
+    /// Resumes the operation of a previously paused timer
+    pub fn resume(&self) -> Result<(), EspError> {
+        esp!(unsafe { ledc_timer_resume(self.speed_mode, T::timer()) })?;
+        Ok(())
+    }
+
+    /// Stops all channels bound to this timer at once and drives their pins
+    /// statically at `idle_level`
+    ///
+    /// This is meant for emergency shutdowns, e.g. from a fault handler, and
+    /// neither allocates nor blocks. The channels are found through a static
+    /// registry, so this works without access to the channel objects. Fades
+    /// in progress get stopped on ESP-IDF 5 and dithering pauses. `idle_level`
+    /// is the actual pin level, [software inversion](Channel::set_inverted())
+    /// doesn't apply.
+    ///
+    /// The channels report [`Stopped`](ChannelState::Stopped) afterwards and
+    /// reject new duties until they get restarted with
+    /// [`enable()`](PwmPin::enable()). All channels get stopped even if the
+    /// SDK fails for one of them, in which case the first error is returned.
+    pub fn stop_all_channels(&self, idle_level: Level) -> Result<(), EspError> {
+        let channels = bound_channels(self.speed_mode, T::timer()).load(Ordering::SeqCst);
+        HALTED_CHANNELS[self.speed_mode as usize].fetch_or(channels, Ordering::SeqCst);
+
+        #[cfg(esp_idf_version_major = "5")]
+        let fading = FADE_CHANNELS[self.speed_mode as usize].load(Ordering::SeqCst);
+
+        let mut result = Ok(());
+        for channel in (0..u32::BITS).filter(|channel| channels & (1 << channel) != 0) {
+            let channel = channel as ledc_channel_t;
+            #[cfg(esp_idf_version_major = "5")]
+            if fading & (1 << channel) != 0 {
+                unsafe { ledc_fade_stop(self.speed_mode, channel) };
+            }
+
+            let stopped = esp!(unsafe { ledc_stop(self.speed_mode, channel, idle_level as u32) });
+            if result.is_ok() {
+                result = stopped;
+            }
+        }
+
+        result
+    }
+
+    /// Resets the counter of the timer to zero
+    ///
+    /// A running timer continues counting from zero while a paused timer
+    /// stays paused at zero until it gets resumed. So pausing several timers,
+    /// resetting them and resuming them afterwards starts them at
+    /// (approximately) the same point.
+    pub fn reset(&self) -> Result<(), EspError> {
+        esp!(unsafe { ledc_timer_rst(self.speed_mode, T::timer()) })?;
+        Ok(())
+    }
+
+    /// Returns the duty producing a high phase of `us` microseconds at the
+    /// current frequency and resolution of the timer
+    ///
+    /// The live frequency as returned by
+    /// [`get_frequency()`](Timer::get_frequency()) is used. The result is
+    /// rounded to the nearest count and clamped to the maximum duty. Negative
+    /// pulse widths and NaN result in a duty of zero.
+    pub fn duty_for_pulse_us(&self, us: f32) -> Result<Duty, EspError> {
+        let frequency = u32::from(self.get_frequency()?) as f32;
+        let counts = (self.resolution.max_duty() + 1) as f32;
+        let duty = us * frequency * counts / 1_000_000.0 + 0.5;
+
+        // Casting saturates and maps NaN to zero.
+        Ok((duty as Duty).min(self.resolution.max_duty()))
+    }
+
+    /// Returns the width of the high phase in microseconds produced by `duty`
+    /// at the current frequency and resolution of the timer
+    ///
+    /// This is the inverse of [`duty_for_pulse_us()`](Timer::duty_for_pulse_us())
+    /// without any rounding. Duties above the maximum duty are clamped.
+    pub fn pulse_us_for_duty(&self, duty: Duty) -> Result<f32, EspError> {
+        let frequency = u32::from(self.get_frequency()?) as f32;
+        let counts = (self.resolution.max_duty() + 1) as f32;
+        let duty = duty.min(self.resolution.max_duty()) as f32;
+
+        Ok(duty * 1_000_000.0 / (frequency * counts))
+    }
+
+    /// Returns the highest frequency this timer can generate at `resolution`
+    /// from its clock source
+    ///
+    /// This is pure arithmetic and can be used for bounding user input before
+    /// calling [`set_frequency()`](Timer::set_frequency()). For
+    /// [`ClockSource::Auto`](config::ClockSource::Auto) the fastest candidate
+    /// clock is assumed.
+    pub fn max_frequency_for(&self, resolution: config::Resolution) -> Hertz {
+        max_frequency(self.clock_source, resolution)
+    }
+
+    /// Returns the lowest frequency this timer can generate at `resolution`
+    /// from its clock source
+    ///
+    /// This is the counterpart to
+    /// [`max_frequency_for()`](Timer::max_frequency_for()) and limited by the
+    /// largest clock divider. For
+    /// [`ClockSource::Auto`](config::ClockSource::Auto) the slowest candidate
+    /// clock is assumed.
+    pub fn min_frequency_for(&self, resolution: config::Resolution) -> Hertz {
+        min_frequency(self.clock_source, resolution)
+    }
+
+    /// Returns the index of the hardware timer for calling SDK functions not
+    /// wrapped by this abstraction
+    pub fn raw_timer(&self) -> ledc_timer_t {
+        T::timer()
+    }
+
+    /// Returns the speed mode of this timer as expected by SDK functions, see
+    /// [`speed_mode()`](Timer::speed_mode()) for the typed one
+    pub fn raw_speed_mode(&self) -> ledc_mode_t {
+        self.speed_mode
+    }
+
+    /// Returns the speed mode this timer has been configured with
+    pub fn speed_mode(&self) -> config::SpeedMode {
+        #[cfg(esp32)]
+        if self.speed_mode == ledc_mode_t_LEDC_HIGH_SPEED_MODE {
+            return config::SpeedMode::HighSpeed;
+        }
+
+        config::SpeedMode::LowSpeed
+    }
+
+    /// Returns the duty resolution this timer has been configured with. This
+    /// is the automatically selected one when using
+    /// [`auto_resolution`](config::TimerConfig::auto_resolution).
+    pub fn resolution(&self) -> config::Resolution {
+        self.resolution
+    }
+
+    /// Stops the timer and releases its hardware resource
+    ///
+    /// The timer gets paused with its counter reset to zero, so channels
+    /// still bound to it stop toggling. Creating a new `Timer` from the
+    /// released instance resumes it.
+    pub fn release(self) -> Result<T, EspError> {
+        self.pause()?;
+        self.reset()?;
+
+        Ok(self.into_instance())
+    }
+
+    /// Releases the timer like [`release()`](Timer::release()) but hands back
+    /// the instance even if pausing or resetting the hardware timer fails
+    pub(crate) fn force_release(self) -> T {
+        let _ = self.pause();
+        let _ = self.reset();
+
+        self.into_instance()
+    }
+
+    /// Unregisters the timer and moves its instance out without touching the
+    /// hardware
+    fn into_instance(self) -> T {
+        // Dropping the timer unregisters it.
+        let this = ManuallyDrop::new(self);
+        // SAFETY: The instance is moved out exactly once and the timer itself
+        // won't be dropped.
+        let instance = unsafe { ptr::read(&this.instance) };
+        this.unregister();
+
+        instance
+    }
+
+    /// Marks this hardware timer as unused and powers down the RC fast clock
+    /// in sleep again if the last timer needing it is gone
+    fn unregister(&self) {
+        let bit = timer_bit(self.speed_mode, T::timer());
+        CONFIGURED_TIMERS.fetch_and(!bit, Ordering::SeqCst);
+
+        if SLEEP_TIMERS.fetch_and(!bit, Ordering::SeqCst) == bit {
+            unsafe {
+                esp_sleep_pd_config(
+                    esp_sleep_pd_domain_t_ESP_PD_DOMAIN_RTC8M,
+                    esp_sleep_pd_option_t_ESP_PD_OPTION_AUTO,
+                )
+            };
+        }
+    }
+}
+
+impl<T: HwTimer, S: HwSpeedMode> core::fmt::Debug for Timer<T, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Timer")
+            .field("timer", &T::timer())
+            .field("speed_mode", &self.speed_mode())
+            .field("requested_frequency", &self.requested_frequency())
+            .field("frequency", &self.get_frequency().ok())
+            .field("resolution", &self.resolution)
+            .field("clock_source", &self.clock_source)
+            .finish()
+    }
+}
+
+/// Prints a summary like `timer0: 25000 Hz, 10-bit`
+///
+/// The frequency is read back from the hardware and therefore shows the
+/// actual frequency instead of the requested one.
+impl<T: HwTimer, S: HwSpeedMode> core::fmt::Display for Timer<T, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "timer{}: ", T::timer())?;
+        match self.get_frequency() {
+            Ok(frequency) => write!(f, "{} Hz", frequency.0)?,
+            Err(_) => write!(f, "? Hz")?,
+        }
+        write!(f, ", {}-bit", self.resolution.bits())
+    }
+}
+
+impl<T: HwTimer, S: HwSpeedMode> Drop for Timer<T, S> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// Error returned by [`Timer::try_new()`] and
+/// [`Timer::try_with_speed_mode()`] along with the instance passed to them
+pub struct TimerSetupError<T> {
+    pub error: LedcError,
+    pub instance: T,
+}
+
+impl<T> From<TimerSetupError<T>> for LedcError {
+    fn from(err: TimerSetupError<T>) -> Self {
+        err.error
+    }
+}
+
+/// Prints the error only
+impl<T> core::fmt::Debug for TimerSetupError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TimerSetupError")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<T> core::fmt::Display for TimerSetupError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Snapshot of a channel's configuration as returned by [`Channel::config()`]
+#[derive(Copy, Clone, Debug)]
+pub struct ChannelInfo {
+    /// Index of the hardware channel
+    pub channel: ledc_channel_t,
+    /// Index of the hardware timer the channel is bound to
+    pub timer: ledc_timer_t,
+    pub speed_mode: config::SpeedMode,
+    /// GPIO the channel outputs to
+    pub gpio: i32,
+    /// Duty cycle last set (or the target of a fade)
+    pub duty: Duty,
+    /// Position within the timer period where the high phase begins
+    pub hpoint: u32,
+}
+
+/// Error returned by [`Channel::try_new_with_config()`] along with the
+/// resources passed to it
+pub struct ChannelSetupError<C, T, P> {
+    /// The stage which failed, usually [`LedcError::ChannelConfig`]
+    pub error: LedcError,
+    pub instance: C,
+    pub timer: T,
+    pub pin: P,
+}
+
+impl<C, T, P> From<ChannelSetupError<C, T, P>> for LedcError {
+    fn from(err: ChannelSetupError<C, T, P>) -> Self {
+        err.error
+    }
+}
+
+/// Prints the error only
+impl<C, T, P> core::fmt::Debug for ChannelSetupError<C, T, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ChannelSetupError")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<C, T, P> core::fmt::Display for ChannelSetupError<C, T, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Whether a [`Channel`] generates its output or has been stopped
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ChannelState {
+    /// The channel outputs its PWM signal
+    Running,
+    /// The channel has been stopped with [`Channel::stop()`] or
+    /// [`disable()`](PwmPin::disable()) and holds its pin at the idle level
+    Stopped,
+}
+
+/// LED Control output channel abstraction
+///
+/// A channel usually borrows its [`Timer`] immutably. So any number of
+/// channels can be created on the same timer and used independently of each
+/// other. A channel can also own its timer by passing it by value, see
+/// [`Channel::simple()`].
+///
+/// Channels are `Send` and can be moved to the task controlling the output
+/// after creating them elsewhere.
+pub struct Channel<
+    'a,
+    C: HwChannel,
+    H: HwTimer,
+    P: OutputPin,
+    S: HwSpeedMode = LowSpeed,
+    T = &'a Timer<H, S>,
+> where
+    T: Borrow<Timer<H, S>>,
+{
+    instance: C,
+    timer: T,
+    pin: P,
+    duty: Duty,
+    max_duty: Duty,
+    idle_level: Level,
+    hpoint: Hpoint,
+    gamma: f32,
+    verify: bool,
+    dither: Option<dither::Dither>,
+    state: ChannelState,
+    /// Whether this channel counts as a user of the fade function
+    fade_func: bool,
+    /// Whether duties get inverted in software, see
+    /// [`Channel::set_inverted()`]
+    inverted: bool,
+    _timer: PhantomData<&'a Timer<H, S>>,
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    Channel<'a, C, H, P, S, T>
+{
+    /// Creates a new LED Control output channel abstraction
+    pub fn new(instance: C, timer: T, pin: P) -> Result<Self, LedcError> {
+        Self::new_with_config(instance, timer, pin, &Default::default())
+    }
+
+    /// Creates a new LED Control output channel abstraction using the given
+    /// channel configuration
+    ///
+    /// Returns [`LedcError::ChannelConfig`] with `ESP_ERR_INVALID_ARG` if the
+    /// configured hpoint lies outside the timer period or the initial duty
+    /// doesn't fit into it. The channel, timer
+    /// and pin get dropped on errors, see
+    /// [`try_new_with_config()`](Channel::try_new_with_config()) for getting
+    /// them back.
+    pub fn new_with_config(
+        instance: C,
+        timer: T,
+        pin: P,
+        config: &config::ChannelConfig,
+    ) -> Result<Self, LedcError> {
+        Self::try_new_with_config(instance, timer, pin, config).map_err(|err| err.error)
+    }
+
+    /// Creates a new LED Control output channel abstraction like
+    /// [`new_with_config()`](Channel::new_with_config()) but hands back the
+    /// channel, timer and pin on errors
+    ///
+    /// The timer is left as configured by [`Timer::new()`] and no channel is
+    /// bound to it when this fails. So setting up the channel can be retried,
+    /// e.g. with a different GPIO or configuration, or the timer can be
+    /// released cleanly.
+    ///
+    /// ```
+    /// use esp_idf_hal::ledc::{Channel, ChannelSetupError};
+    ///
+    /// let channel = match Channel::try_new_with_config(channel0, &timer, gpio1, &config) {
+    ///     Ok(channel) => channel,
+    ///     Err(ChannelSetupError { error, instance, timer, .. }) => {
+    ///         log::warn!("falling back to GPIO2: {}", error);
+    ///         Channel::new_with_config(instance, timer, gpio2, &config)?
+    ///     }
+    /// };
+    /// ```
+    pub fn try_new_with_config(
+        instance: C,
+        timer: T,
+        pin: P,
+        config: &config::ChannelConfig,
+    ) -> Result<Self, ChannelSetupError<C, T, P>> {
+        match Self::configure(&timer, &pin, config) {
+            Ok((hpoint, max_duty)) => Ok(Channel {
+                instance,
+                timer,
+                pin,
+                duty: config.initial_duty,
+                max_duty,
+                idle_level: config.idle_level,
+                hpoint,
+                gamma: config.gamma,
+                verify: config.verify,
+                dither: None,
+                state: ChannelState::Running,
+                fade_func: config.install_fade,
+                inverted: false,
+                _timer: PhantomData,
+            }),
+            Err(error) => Err(ChannelSetupError {
+                error,
+                instance,
+                timer,
+                pin,
+            }),
+        }
+    }
+
+    /// Configures the hardware channel and returns its hpoint and maximum
+    /// duty
+    ///
+    /// Nothing is left behind on errors, i.e. the fade function is released
+    /// again.
+    fn configure(
+        timer: &T,
+        pin: &P,
+        config: &config::ChannelConfig,
+    ) -> Result<(Hpoint, Duty), LedcError> {
+        let speed_mode = timer.borrow().speed_mode;
+        let resolution = timer.borrow().resolution;
+        let max_duty = resolution.max_duty();
+        let hpoint = Hpoint::new(config.hpoint, resolution).map_err(LedcError::ChannelConfig)?;
+        let duty = config.initial_duty;
+        if duty > max_duty || hpoint.get() + duty > max_duty + 1 {
+            return Err(LedcError::ChannelConfig(
+                EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap(),
+            ));
+        }
+
+        let mut flags = ledc_channel_config_t__bindgen_ty_1::default();
+        flags.set_output_invert(config.invert as u32);
+
+        let channel_config = ledc_channel_config_t {
+            speed_mode,
+            channel: C::channel(),
+            timer_sel: H::timer(),
+            intr_type: config.interrupt_type.into(),
+            gpio_num: pin.pin(),
+            duty,
+            hpoint: hpoint.for_config(),
+            flags,
+        };
+
+        // The fade function is needed for fades, the fade end callback and
+        // ledc_set_duty_and_update. Channels not installing it up front do so
+        // on demand with ensure_fade_func.
+        if config.install_fade {
+            acquire_fade_func().map_err(LedcError::FadeFuncInstall)?;
+        }
+
+        // SAFETY: As long as we have borrowed the timer, we are safe to use
+        // it.
+        if let Err(err) = esp!(unsafe { ledc_channel_config(&channel_config) }) {
+            if config.install_fade {
+                release_fade_func();
+            }
+            return Err(LedcError::ChannelConfig(err));
+        }
+
+        #[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+        if config.install_fade {
+            if let Err(err) = fade_end::register(speed_mode, C::channel()) {
+                unsafe { ledc_stop(speed_mode, C::channel(), config.idle_level as u32) };
+                release_fade_func();
+                return Err(LedcError::ChannelConfig(err));
+            }
+        } else {
+            fade_end::reset(speed_mode, C::channel());
+        }
+
+        bound_channels(speed_mode, H::timer()).fetch_or(1 << C::channel(), Ordering::SeqCst);
+        HALTED_CHANNELS[speed_mode as usize].fetch_and(!(1 << C::channel()), Ordering::SeqCst);
+        if config.install_fade {
+            FADE_CHANNELS[speed_mode as usize].fetch_or(1 << C::channel(), Ordering::SeqCst);
+        }
+
+        Ok((hpoint, max_duty))
+    }
+
+    /// Stops the output channel and releases its hardware resource and GPIO
+    /// pin
+    pub fn release(mut self) -> Result<(C, P), EspError> {
+        self.stop(self.idle_level)?;
+        self.unregister();
+
+        // The channel has already been stopped and has released the fade
+        // function, so skip doing this once more when dropping it.
+        let this = ManuallyDrop::new(self);
+        // SAFETY: The fields are moved out exactly once and the channel itself
+        // won't be dropped.
+        let instance = unsafe { ptr::read(&this.instance) };
+        let pin = unsafe { ptr::read(&this.pin) };
+        drop(unsafe { ptr::read(&this.timer) });
+        drop(unsafe { ptr::read(&this.dither) });
+
+        Ok((instance, pin))
+    }
+
+    /// Routes the output of this channel to another pin and returns the
+    /// previous one
+    ///
+    /// The previous pin gets reset to its default state so that it no longer
+    /// outputs the PWM signal. Pins of different GPIOs can be used with the
+    /// same channel by [degrading](crate::gpio::Gpio0::degrade()) them
+    /// to [`GpioPin`](crate::gpio::GpioPin).
+    pub fn set_pin(&mut self, pin: P) -> Result<P, EspError> {
+        esp!(unsafe { ledc_set_pin(pin.pin(), self.timer().speed_mode, C::channel()) })?;
+        esp!(unsafe { gpio_reset_pin(self.pin.pin()) })?;
+
+        Ok(core::mem::replace(&mut self.pin, pin))
+    }
+
+    /// Binds this channel to another timer and returns it as a channel of
+    /// this timer
+    ///
+    /// The timer has to use the same speed mode, which is enforced by its
+    /// type. The duty is kept and clamped to the maximum duty of the new
+    /// timer. An hpoint outside the period of the new timer is reset to zero.
+    /// An owned previous timer gets dropped.
+    pub fn bind_timer<'b, H2: HwTimer, T2: Borrow<Timer<H2, S>>>(
+        self,
+        timer: T2,
+    ) -> Result<Channel<'b, C, H2, P, S, T2>, EspError> {
+        let speed_mode = timer.borrow().speed_mode;
+        let resolution = timer.borrow().resolution;
+        let max_duty = resolution.max_duty();
+
+        esp!(unsafe { ledc_bind_channel_timer(speed_mode, C::channel(), H2::timer()) })?;
+        bound_channels(speed_mode, H::timer()).fetch_and(!(1 << C::channel()), Ordering::SeqCst);
+        bound_channels(speed_mode, H2::timer()).fetch_or(1 << C::channel(), Ordering::SeqCst);
+
+        // The hardware channel lives on, so move it over without stopping it
+        // and without releasing the fade function.
+        let this = ManuallyDrop::new(self);
+        // SAFETY: The fields are moved out exactly once and the channel itself
+        // won't be dropped.
+        let (instance, pin, dither) = unsafe {
+            (
+                ptr::read(&this.instance),
+                ptr::read(&this.pin),
+                ptr::read(&this.dither),
+            )
+        };
+        drop(unsafe { ptr::read(&this.timer) });
+
+        let mut channel = Channel {
+            instance,
+            timer,
+            pin,
+            duty: this.duty,
+            max_duty,
+            idle_level: this.idle_level,
+            hpoint: Hpoint::new(this.hpoint.get(), resolution).unwrap_or_default(),
+            gamma: this.gamma,
+            verify: this.verify,
+            dither,
+            state: this.state,
+            fade_func: this.fade_func,
+            inverted: this.inverted,
+            _timer: PhantomData,
+        };
+
+        // Inverted duties depend on the maximum duty and need to be rewritten
+        // for a different resolution as well.
+        let rescaled = this.inverted && this.max_duty != max_duty;
+        if channel.duty > channel.max_duty || rescaled {
+            channel.duty = channel.duty.min(channel.max_duty);
+            if channel.state() == ChannelState::Running {
+                channel.update_duty(channel.duty, channel.hpoint)?;
+            }
+        }
+
+        Ok(channel)
+    }
+
+    /// Enables or disables reading back the duty from the hardware after
+    /// setting it
+    ///
+    /// With verification enabled, setting a duty (other than staging it or
+    /// fading) waits until the hardware reports the new duty with
+    /// `ledc_get_duty` and returns `ESP_ERR_INVALID_STATE` on a mismatch.
+    /// The new duty only latches with the next overflow of the timer. So this
+    /// busy-waits for up to two PWM periods, which is negligible at kHz
+    /// frequencies but noticeable for slow timers. This helps uncovering
+    /// misconfigurations like a channel bound to an unexpected timer.
+    /// Dithered duties are not verified.
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Returns a snapshot of the channel's current configuration for
+    /// inspection
+    pub fn config(&self) -> ChannelInfo {
+        ChannelInfo {
+            channel: C::channel(),
+            timer: H::timer(),
+            speed_mode: self.timer().speed_mode(),
+            gpio: self.pin.pin(),
+            duty: self.duty,
+            hpoint: self.hpoint.get(),
+        }
+    }
+
+    /// Returns the index of the hardware channel for calling SDK functions
+    /// not wrapped by this abstraction
+    ///
+    /// Changing duty or configuration through the SDK bypasses the state
+    /// cached by the channel, e.g. the duty returned by
+    /// [`get_duty()`](PwmPin::get_duty()).
+    pub fn raw_channel(&self) -> ledc_channel_t {
+        C::channel()
+    }
+
+    /// Returns the index of the hardware timer this channel is bound to
+    ///
+    /// [`bind_timer()`](Channel::bind_timer()) changes the timer type of the
+    /// channel, so this always reflects the current binding without asking
+    /// the SDK.
+    pub fn timer_index(&self) -> ledc_timer_t {
+        H::timer()
+    }
+
+    /// Returns the timer this channel is bound to
+    pub fn timer(&self) -> &Timer<H, S> {
+        self.timer.borrow()
+    }
+
+    /// Stops the output and drives the pin statically at `idle_level`
+    ///
+    /// Other than setting the duty to zero, this halts the output completely.
+    /// The channel is [`Stopped`](ChannelState::Stopped) afterwards and
+    /// setting a duty or starting a fade returns `ESP_ERR_INVALID_STATE`
+    /// instead of silently writing to the halted hardware. The output can
+    /// be restarted with its last duty with [`enable()`](PwmPin::enable()).
+    /// The pin is driven at the opposite level with
+    /// [software inversion](Channel::set_inverted()) enabled.
+    pub fn stop(&mut self, idle_level: Level) -> Result<(), EspError> {
+        if let Some(dither) = &mut self.dither {
+            dither.stop()?;
+        }
+
+        let level = idle_level as u32 ^ self.inverted as u32;
+        esp!(unsafe { ledc_stop(self.timer().speed_mode, C::channel(), level) })?;
+        self.state = ChannelState::Stopped;
+        Ok(())
+    }
+
+    /// Enables or disables inverting the output in software
+    ///
+    /// With inversion enabled, `max_duty - duty` gets written to the hardware
+    /// for every duty and fade target and stopping the channel drives the
+    /// pin at the opposite of the requested idle level. So an active-low
+    /// load behaves like an active-high one. [`get_duty()`](PwmPin::get_duty())
+    /// and [`get_hw_duty()`](Channel::get_hw_duty()) keep reporting logical
+    /// duties. As the hardware tops out at the maximum duty, a logical duty of
+    /// zero still leaves a high phase of one count per period.
+    ///
+    /// This is the fallback for setups where the hardware inversion from
+    /// [`ChannelConfig::invert`](config::ChannelConfig::invert) in the GPIO
+    /// matrix is not an option, e.g. for a pin shared through
+    /// [`new_on_shared_pin()`](Channel::new_on_shared_pin()). A running
+    /// channel gets updated right away, a stopped one keeps its pin level
+    /// until it's stopped again. Returns `ESP_ERR_INVALID_STATE` while
+    /// dithering is enabled.
+    pub fn set_inverted(&mut self, inverted: bool) -> Result<(), EspError> {
+        self.check_no_dither()?;
+        if inverted == self.inverted {
+            return Ok(());
+        }
+
+        self.inverted = inverted;
+        match self.state() {
+            ChannelState::Running => self.update_duty(self.duty, self.hpoint),
+            ChannelState::Stopped => Ok(()),
+        }
+    }
+
+    /// Returns whether the output is inverted in software
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Borrows this channel as PWM output with a fixed 16 bit duty scale
+    /// for drivers which assume a maximum duty of `u16::MAX`, see [`U16Pwm`]
+    pub fn as_u16_pwm(&mut self) -> U16Pwm<'_, Self> {
+        U16Pwm::new(self)
+    }
+
+    /// Returns whether the channel is running or has been stopped
+    ///
+    /// This includes channels halted with [`Timer::stop_all_channels()`].
+    pub fn state(&self) -> ChannelState {
+        if channel_halted(self.timer().speed_mode, C::channel()) {
+            ChannelState::Stopped
+        } else {
+            self.state
+        }
+    }
+
+    /// Sets the duty cycle and the position within the timer period where the
+    /// high phase begins
+    ///
+    /// Shifting `hpoint` allows phase-shifted outputs of channels sharing a
+    /// timer. Returns `ESP_ERR_INVALID_ARG` if the high phase would exceed the
+    /// timer period, i.e. `hpoint + duty` is larger than
+    /// [`get_max_duty()`](PwmPin::get_max_duty()) + 1.
+    ///
+    /// The hpoint replaces the one from the channel configuration, like for
+    /// [`set_duty_with_hpoint_staged()`](Channel::set_duty_with_hpoint_staged()).
+    /// So later duty updates, restarting with [`enable()`](PwmPin::enable())
+    /// and [`config()`](Channel::config()) keep referring to the phase on the
+    /// pin.
+    pub fn set_duty_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError> {
+        let hpoint = self.check_duty_with_hpoint(duty, hpoint)?;
+
+        self.update_duty(duty, hpoint)?;
+
+        self.duty = duty;
+        self.hpoint = hpoint;
+        Ok(())
+    }
+
+    /// Restarts the output with a new duty cycle and hpoint instead of the
+    /// last ones
+    ///
+    /// Other than [`enable()`](PwmPin::enable()) followed by
+    /// [`set_duty_with_hpoint()`](Channel::set_duty_with_hpoint()), the
+    /// output never runs with the previous duty and phase in between. This
+    /// matters when the last ones would overlap with another output, like
+    /// the legs of an [`HBridge`](hbridge::HBridge). For a running channel
+    /// this is the same as `set_duty_with_hpoint()`. Returns
+    /// `ESP_ERR_INVALID_ARG` like the latter.
+    pub fn restart_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError> {
+        let hpoint = self.check_duty_with_hpoint(duty, hpoint)?;
+
+        self.start(duty, hpoint)?;
+
+        self.duty = duty;
+        self.hpoint = hpoint;
+        Ok(())
+    }
+
+    /// Sets the duty cycle like [`set_duty()`](PwmPin::set_duty()) and
+    /// reports whether it saturated
+    ///
+    /// Duties above [`get_max_duty()`](PwmPin::get_max_duty()) are clamped to
+    /// the maximum duty before passing them to the SDK. Returns `true` if
+    /// `duty` has been clamped this way and `false` if it has been output as
+    /// given.
+    pub fn set_duty_saturating(&mut self, duty: Duty) -> Result<bool, EspError> {
+        PwmPin::set_duty(self, duty)?;
+        Ok(duty > self.max_duty)
+    }
+
+    /// Changes the duty cycle by `delta` counts, saturating at zero and the
+    /// maximum duty
+    ///
+    /// This suits incremental dimming, e.g. with a rotary encoder calling
+    /// `increment_duty(1)` and `increment_duty(-1)`. Returns the new duty.
+    pub fn increment_duty(&mut self, delta: i32) -> Result<Duty, EspError> {
+        let duty = (self.duty as i64 + delta as i64).clamp(0, self.max_duty as i64) as Duty;
+        PwmPin::set_duty(self, duty)?;
+        Ok(duty)
+    }
+
+    /// Sets the duty cycle like [`set_duty()`](PwmPin::set_duty()) but
+    /// always writes it to the hardware
+    ///
+    /// [`set_duty()`](PwmPin::set_duty()) skips the register write and latch
+    /// when the duty doesn't change, which saves CPU time in control loops
+    /// setting the duty on every tick. Use this when the hardware may differ
+    /// from the channel's duty, e.g. for interrupting a fade towards the same
+    /// duty, after a staged duty hasn't been latched or after changing the
+    /// channel through the SDK directly.
+    pub fn force_update(&mut self, duty: Duty) -> Result<(), EspError> {
+        let duty = duty.min(self.max_duty);
+        self.update_duty(duty, self.hpoint)?;
+        self.duty = duty;
+        Ok(())
+    }
+
+    /// Stages a new duty cycle without applying it yet
+    ///
+    /// The duty (clamped to the maximum duty) gets written with
+    /// `ledc_set_duty` and takes effect with the next PWM period after calling
+    /// [`latch()`](Channel::latch()). This allows preparing the duties of
+    /// several channels and latching them in a tight sequence, see
+    /// [`DutyGroup`] for a convenient wrapper. The staged duty already becomes
+    /// the channel's duty.
+    pub fn set_duty_staged(&mut self, duty: Duty) -> Result<(), EspError> {
+        self.check_running()?;
+        let duty = duty.min(self.max_duty);
+        esp!(unsafe {
+            ledc_set_duty_with_hpoint(
+                self.timer().speed_mode,
+                C::channel(),
+                self.to_hw(duty),
+                self.hpoint.get(),
+            )
+        })?;
+
+        self.duty = duty;
+        Ok(())
+    }
+
+    /// Stages a new duty cycle together with the position within the timer
+    /// period where the high phase begins without applying them yet
+    ///
+    /// This is the phase-aware variant of
+    /// [`set_duty_staged()`](Channel::set_duty_staged()). Both get written
+    /// with `ledc_set_duty_with_hpoint` and take effect together with the
+    /// next PWM period after calling [`latch()`](Channel::latch()). So duty and
+    /// phase of several channels, e.g. of an interleaved converter, can be
+    /// switched over in the same period. Returns `ESP_ERR_INVALID_ARG` like
+    /// [`set_duty_with_hpoint()`](Channel::set_duty_with_hpoint()) if the high
+    /// phase would exceed the timer period.
+    pub fn set_duty_with_hpoint_staged(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError> {
+        self.check_running()?;
+        let hpoint = self.check_duty_with_hpoint(duty, hpoint)?;
+
+        esp!(unsafe {
+            ledc_set_duty_with_hpoint(
+                self.timer().speed_mode,
+                C::channel(),
+                self.to_hw(duty),
+                hpoint.get(),
+            )
+        })?;
+
+        self.duty = duty;
+        self.hpoint = hpoint;
+        Ok(())
+    }
+
+    /// Applies a duty staged with
+    /// [`set_duty_staged()`](Channel::set_duty_staged()) or
+    /// [`set_duty_with_hpoint_staged()`](Channel::set_duty_with_hpoint_staged())
+    /// using `ledc_update_duty`
+    pub fn latch(&mut self) -> Result<(), EspError> {
+        self.check_running()?;
+        esp!(unsafe { ledc_update_duty(self.timer().speed_mode, C::channel()) })?;
+        if let Some(dither) = &mut self.dither {
+            dither.set(self.duty, 0, self.hpoint.get())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the actual period of the PWM signal as generated by the timer
+    pub fn get_period(&self) -> Result<Duration, EspError> {
+        self.timer().get_period()
+    }
+
+    /// Returns the duty cycle currently output by the hardware
+    ///
+    /// Other than [`get_duty()`](PwmPin::get_duty()), which returns the last
+    /// duty set (or the target of a fade), this tracks the duty while a
+    /// hardware fade is in progress. The cached duty is left untouched. With
+    /// [software inversion](Channel::set_inverted()) enabled, this is the
+    /// logical duty as well.
+    pub fn get_hw_duty(&self) -> Duty {
+        let duty = unsafe { ledc_get_duty(self.timer().speed_mode, C::channel()) };
+        self.to_hw(duty)
+    }
+
+    /// Sets the duty cycle as a fraction of the maximum duty
+    ///
+    /// The fraction gets clamped to `0.0..=1.0` and NaN is treated as `0.0`.
+    /// With [dithering](Channel::enable_dithering()) enabled, fractions
+    /// between two duty values are emulated by alternating between them and
+    /// [`get_duty()`](PwmPin::get_duty()) reports the lower one.
+    pub fn set_duty_fraction(&mut self, fraction: f32) -> Result<(), EspError> {
+        let fraction = clamp_fraction(fraction);
+
+        let depth = match &self.dither {
+            Some(dither) => dither.depth(),
+            None => {
+                let duty = (fraction * self.max_duty as f32 + 0.5) as Duty;
+                return PwmPin::set_duty(self, duty);
+            }
+        };
+
+        let scaled = (fraction * (self.max_duty << depth) as f32 + 0.5) as u32;
+        let duty = (scaled >> depth).min(self.max_duty);
+        let remainder = if duty < self.max_duty {
+            scaled & ((1 << depth) - 1)
+        } else {
+            0
+        };
+
+        PwmPin::set_duty(self, duty)?;
+        if let Some(dither) = &mut self.dither {
+            dither.set(duty, remainder, self.hpoint.get())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the duty cycle as a fraction of the maximum duty within
+    /// `0.0..=1.0`
+    ///
+    /// Like [`get_duty()`](PwmPin::get_duty()), this is based on the last duty
+    /// set (or the target of a fade). With dithering enabled, this is the
+    /// lower of the two alternating duty values.
+    pub fn get_duty_fraction(&self) -> f32 {
+        self.duty as f32 / self.max_duty as f32
+    }
+
+    /// Enables software dithering for emulating `depth_bits` of additional
+    /// duty resolution with [`set_duty_fraction()`](Channel::set_duty_fraction())
+    ///
+    /// A periodic `esp_timer` alternates the output between the two duty
+    /// values bracketing the requested fraction, once per PWM period but at
+    /// most every 100 µs. This is meant for dimming LEDs where the hardware
+    /// resolution is limited by a high frequency and comes at the cost of
+    /// some CPU load. `depth_bits` has to be within `1..=8`, otherwise
+    /// `ESP_ERR_INVALID_ARG` gets returned.
+    ///
+    /// Hardware fades are not available while dithering is enabled and
+    /// return `ESP_ERR_INVALID_STATE`. Dithering is not available with
+    /// [software inversion](Channel::set_inverted()) and returns
+    /// `ESP_ERR_INVALID_STATE` as well.
+    pub fn enable_dithering(&mut self, depth_bits: u8) -> Result<(), EspError> {
+        if !(1..=8).contains(&depth_bits) {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+        if self.inverted {
+            return Err(EspError::from(ESP_ERR_INVALID_STATE as i32).unwrap());
+        }
+
+        match &mut self.dither {
+            Some(dither) => dither.set_depth(depth_bits),
+            None => {
+                // The dither timer updates the duty with
+                // ledc_set_duty_and_update.
+                self.ensure_fade_func()?;
+                self.dither = Some(dither::Dither::new(
+                    self.timer().speed_mode,
+                    C::channel(),
+                    depth_bits,
+                    self.timer().get_period()?,
+                )?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Disables software dithering and leaves the output at the lower of
+    /// the two alternating duty values
+    pub fn disable_dithering(&mut self) -> Result<(), EspError> {
+        if self.dither.take().is_some() && self.state() == ChannelState::Running {
+            self.update_duty(self.duty, self.hpoint)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the perceived brightness of a LED within `0.0..=1.0`
+    ///
+    /// The brightness gets gamma corrected with the exponent from
+    /// [`ChannelConfig::gamma`](config::ChannelConfig::gamma) (2.2 by
+    /// default) for getting a visually linear response. Values outside the
+    /// range are clamped and NaN is treated as `0.0`.
+    #[cfg(feature = "std")]
+    pub fn set_brightness(&mut self, perceived: f32) -> Result<(), EspError> {
+        self.set_duty_fraction(clamp_fraction(perceived).powf(self.gamma))
+    }
+
+    /// Sets the perceived brightness of a LED within `0.0..=1.0` using a
+    /// custom lookup table
+    ///
+    /// The table holds duty fractions for evenly spaced brightness values,
+    /// from `0.0` for the first to `1.0` for the last entry. Values in between
+    /// are interpolated linearly. Returns `ESP_ERR_INVALID_ARG` for an empty
+    /// table.
+    pub fn set_brightness_from_table(
+        &mut self,
+        perceived: f32,
+        table: &[f32],
+    ) -> Result<(), EspError> {
+        let fraction = match table.len() {
+            0 => return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap()),
+            1 => table[0],
+            len => {
+                let position = clamp_fraction(perceived) * (len - 1) as f32;
+                let index = (position as usize).min(len - 2);
+                let weight = position - index as f32;
+
+                table[index] + (table[index + 1] - table[index]) * weight
+            }
+        };
+
+        self.set_duty_fraction(fraction)
+    }
+
+    /// Sets the duty cycle in percent of the maximum duty
+    ///
+    /// This takes plain numbers as well as [`DutyPercent`] created with
+    /// `50.percent()`. The duty gets computed from the timer's actual
+    /// resolution. Values above 100 are clamped to 100.
+    pub fn set_duty_percent(&mut self, percent: impl Into<DutyPercent>) -> Result<(), EspError> {
+        let percent = percent.into().0.min(100) as Duty;
+        let duty = (self.max_duty * percent + 50) / 100;
+
+        PwmPin::set_duty(self, duty)
+    }
+
+    /// Returns the duty cycle in percent of the maximum duty, rounded to the
+    /// nearest integer
+    pub fn get_duty_percent(&self) -> u8 {
+        ((self.duty * 100 + self.max_duty / 2) / self.max_duty) as u8
+    }
+
+    /// Fades the duty cycle to `target_duty` within `duration` using the
+    /// hardware fade function
+    ///
+    /// This starts the fade and returns immediately. The target is clamped to
+    /// the maximum duty and becomes the channel's duty right away.
+    ///
+    /// The SDK works in whole milliseconds. So `duration` gets rounded up to
+    /// the next millisecond and zero-length fades take 1 ms.
+    pub fn fade_with_time(
+        &mut self,
+        target_duty: Duty,
+        duration: Duration,
+    ) -> Result<(), EspError> {
+        self.set_fade_time_and_start(target_duty, duration, FadeMode::NoWait)
+    }
+
+    /// Fades the duty cycle to `target_duty` within `duration` and either
+    /// returns immediately or waits for the end of the fade according to
+    /// `mode`
+    ///
+    /// The target is clamped to the maximum duty and becomes the channel's
+    /// duty right away. Errors from the SDK rejecting the fade parameters are
+    /// passed on.
+    pub fn set_fade_time_and_start(
+        &mut self,
+        target_duty: Duty,
+        duration: Duration,
+        mode: FadeMode,
+    ) -> Result<(), EspError> {
+        let target_duty = self.set_fade_with_time(target_duty, duration)?;
+        self.start_fade(mode.into())?;
+
+        self.duty = target_duty;
+        Ok(())
+    }
+
+    /// Fades the duty cycle to `target_duty` within `duration` and jumps to
+    /// `hold_duty` right when the fade ends, e.g. for a "blip then settle"
+    /// indicator
+    ///
+    /// The fade end callback writes `hold_duty` from ISR context, so it takes
+    /// effect with the PWM period following the end of the fade, independent
+    /// of task scheduling. This returns right after starting the fade. Both
+    /// duties are clamped to the maximum duty and `hold_duty` becomes the
+    /// channel's duty right away.
+    #[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+    pub fn fade_then_hold(
+        &mut self,
+        target_duty: Duty,
+        duration: Duration,
+        hold_duty: Duty,
+    ) -> Result<(), EspError> {
+        let hold_duty = hold_duty.min(self.max_duty);
+        self.set_fade_with_time(target_duty, duration)?;
+
+        let speed_mode = self.timer().speed_mode;
+        fade_end::hold_at_end(speed_mode, C::channel(), self.to_hw(hold_duty));
+        if let Err(err) = self.start_fade(ledc_fade_mode_t_LEDC_FADE_NO_WAIT) {
+            fade_end::cancel_hold(speed_mode, C::channel());
+            return Err(err);
+        }
+
+        self.duty = hold_duty;
+        Ok(())
+    }
+
+    /// Fades the duty cycle to `target_duty` within `duration` and blocks
+    /// until the fade is done
+    ///
+    /// Without a `timeout` this waits for the hardware to signal the end of
+    /// the fade. With a `timeout`, the duty cycle is polled each tick and
+    /// `ESP_ERR_TIMEOUT` gets returned if the target has not been reached in
+    /// time. The fade keeps running in this case.
+    pub fn fade_with_time_blocking(
+        &mut self,
+        target_duty: Duty,
+        duration: Duration,
+        timeout: Option<Duration>,
+    ) -> Result<(), EspError> {
+        let target_duty = self.set_fade_with_time(target_duty, duration)?;
+
+        if let Some(timeout) = timeout {
+            self.start_fade(ledc_fade_mode_t_LEDC_FADE_NO_WAIT)?;
+            self.duty = target_duty;
+
+            let mut ticks = TickType::from(timeout).0;
+            while self.get_hw_duty() != target_duty {
+                if ticks == 0 {
+                    return Err(EspError::from(ESP_ERR_TIMEOUT as i32).unwrap());
+                }
+                unsafe { vTaskDelay(1) };
+                ticks -= 1;
+            }
+        } else {
+            self.start_fade(ledc_fade_mode_t_LEDC_FADE_WAIT_DONE)?;
+            self.duty = target_duty;
+        }
+
+        Ok(())
+    }
+
+    /// Fades the duty cycle to `target_duty` within `duration` and completes
+    /// when the fade is done
+    ///
+    /// The end of the fade is signalled from ISR context by the fade end
+    /// callback registered with `ledc_cb_register`, which wakes the awaiting
+    /// task. Therefore the waker of the executor has to be safe to use from an
+    /// ISR. The channel's duty is updated to the target once the fade is done.
+    #[cfg(all(
+        feature = "experimental",
+        any(esp_idf_version = "4.4", esp_idf_version_major = "5")
+    ))]
+    pub async fn fade_with_time_async(
+        &mut self,
+        target_duty: Duty,
+        duration: Duration,
+    ) -> Result<(), EspError> {
+        let fade_end = fade_end::next(self.timer().speed_mode, C::channel());
+        let target_duty = self.set_fade_with_time(target_duty, duration)?;
+        self.start_fade(ledc_fade_mode_t_LEDC_FADE_NO_WAIT)?;
+
+        fade_end.await;
+
+        self.duty = target_duty;
+        Ok(())
+    }
+
+    /// Fades the duty cycle to `target_duty` in steps using the hardware fade
+    /// function
+    ///
+    /// The duty changes by `scale` counts every `cycle_num` PWM periods until
+    /// the target is reached. So the fade takes about
+    /// `|target_duty - duty| / scale * cycle_num` periods. This starts the fade
+    /// and returns immediately.
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` if `target_duty` exceeds the maximum duty.
+    pub fn fade_with_step(
+        &mut self,
+        target_duty: Duty,
+        scale: u32,
+        cycle_num: u32,
+    ) -> Result<(), EspError> {
+        self.check_running()?;
+        self.check_no_dither()?;
+        self.ensure_fade_func()?;
+        if target_duty > self.max_duty {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        esp!(unsafe {
+            ledc_set_fade_with_step(
+                self.timer().speed_mode,
+                C::channel(),
+                self.to_hw(target_duty),
+                scale,
+                cycle_num,
+            )
+        })?;
+        self.start_fade(ledc_fade_mode_t_LEDC_FADE_NO_WAIT)?;
+
+        self.duty = target_duty;
+        Ok(())
+    }
+
+    /// Ramps the duty cycle linearly to `target` in `steps` software steps
+    /// and calls `per_step` after each of them
+    ///
+    /// Unlike the hardware fades, this blocks and occupies the CPU for the
+    /// whole ramp. Its pace is set by `per_step` alone, which may for example
+    /// read a sensor or delay. The last step always lands exactly on
+    /// `target`. A `steps` of zero is treated as a single step.
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` if `target` exceeds the maximum duty.
+    pub fn ramp_duty(
+        &mut self,
+        target: Duty,
+        steps: u32,
+        mut per_step: impl FnMut(),
+    ) -> Result<(), EspError> {
+        if target > self.max_duty {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        let start = self.duty;
+        let steps = steps.max(1);
+        for step in 1..=steps {
+            // Interpolating from the start for each step avoids accumulating
+            // rounding errors.
+            let delta = |distance: Duty| (distance as u64 * step as u64 / steps as u64) as Duty;
+            let duty = if target >= start {
+                start + delta(target - start)
+            } else {
+                start - delta(start - target)
+            };
+
+            PwmPin::set_duty(self, duty)?;
+            per_step();
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of hardware fades completed on this channel since
+    /// its creation
+    ///
+    /// The fade end callback counts fades atomically from ISR context, so
+    /// this can be polled for checking whether a sequence of queued fades is
+    /// done. The count wraps around after `u32::MAX` fades as there are no
+    /// 64 bit atomics on the ESP32 family.
+    #[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+    pub fn fades_completed(&self) -> u32 {
+        fade_end::completed(self.timer().speed_mode, C::channel())
+    }
+
+    /// Emits `count` PWM pulses at the current duty and stops the output
+    /// afterwards, e.g. for single-stepping a stepper motor driver
+    ///
+    /// This uses a hardware fade by a single duty step which takes `count`
+    /// periods. Its fade end callback stops the output from ISR context. For
+    /// giving the ISR as much time as possible, the high phase gets moved to
+    /// the end of the period, so the ISR has to run within the low phase of
+    /// the period following the last pulse. This holds for the typical step
+    /// rates of stepper motors but not for frequencies in the MHz range.
+    ///
+    /// The call returns right after starting the pulses and the channel is
+    /// [`Stopped`](ChannelState::Stopped) from then on. Poll
+    /// [`fades_completed()`](Channel::fades_completed()) for detecting the
+    /// end and restart continuous output with [`enable()`](PwmPin::enable()).
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` for a count outside
+    /// `1..=`[`MAX_PULSE_COUNT`] or a duty of zero, as there are no pulses
+    /// then. The hpoint of `max_duty + 1 - duty` gets validated like for
+    /// [`set_duty_with_hpoint()`](Channel::set_duty_with_hpoint()) before
+    /// passing it to the SDK. `ESP_ERR_INVALID_STATE` is returned while
+    /// dithering or [software inversion](Channel::set_inverted()) is enabled. Channels
+    /// halted by [`Timer::stop_all_channels()`] stay off and report
+    /// `ESP_ERR_INVALID_STATE` as well until they get restarted with
+    /// [`enable()`](PwmPin::enable()).
+    #[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+    pub fn pulse_n(&mut self, count: u32) -> Result<(), EspError> {
+        self.check_no_dither()?;
+        // Other than a halted channel, a stopped one may emit pulses, e.g.
+        // for stepping repeatedly.
+        if self.inverted || channel_halted(self.timer().speed_mode, C::channel()) {
+            return Err(EspError::from(ESP_ERR_INVALID_STATE as i32).unwrap());
+        }
+        self.ensure_fade_func()?;
+        // The fade starts at the duty currently output by the hardware.
+        let duty = self.get_hw_duty().min(self.max_duty);
+        if !(1..=MAX_PULSE_COUNT).contains(&count) || duty == 0 {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        // The high phase ends with the period. This keeps the hpoint within
+        // the period as the duty is at least one.
+        let hpoint = self.check_duty_with_hpoint(duty, self.max_duty + 1 - duty)?;
+
+        let speed_mode = self.timer().speed_mode;
+        let target = if duty < self.max_duty {
+            duty + 1
+        } else {
+            duty - 1
+        };
+
+        // The hpoint only latches together with the fade start while the
+        // fade configuration leaves it unchanged.
+        esp!(unsafe { ledc_set_duty_with_hpoint(speed_mode, C::channel(), duty, hpoint.get()) })?;
+        esp!(unsafe { ledc_set_fade_with_step(speed_mode, C::channel(), target, 1, count) })?;
+
+        fade_end::stop_at_end(speed_mode, C::channel(), self.idle_level as u32);
+        if let Err(err) = self.start_fade(ledc_fade_mode_t_LEDC_FADE_NO_WAIT) {
+            fade_end::cancel_stop(speed_mode, C::channel());
+            return Err(err);
+        }
+
+        self.state = ChannelState::Stopped;
+        Ok(())
+    }
+
+    /// Stops a hardware fade in progress and takes over the duty reached so
+    /// far as the channel's duty
+    ///
+    /// This requires the fade function to be installed, which gets done on
+    /// demand. Stopping is a no-op if no fade is in progress.
+    ///
+    /// # Availability
+    ///
+    /// This is only available when building against ESP-IDF 5 as
+    /// `ledc_fade_stop` doesn't exist in earlier versions. The `esp-idf-sys`
+    /// 0.30 this crate currently depends on targets the ESP-IDF 4.x releases,
+    /// so the method is not available in typical builds yet. The SDK of ESP-IDF 4.4 offers no way to
+    /// abort a fade: it blocks any duty update of a fading channel until the
+    /// fade is done. Starting fades with short durations and chaining them
+    /// keeps the time before taking over short there.
+    #[cfg(esp_idf_version_major = "5")]
+    pub fn fade_stop(&mut self) -> Result<(), EspError> {
+        self.ensure_fade_func()?;
+        esp!(unsafe { ledc_fade_stop(self.timer().speed_mode, C::channel()) })?;
+
+        self.duty = self.get_hw_duty();
+        Ok(())
+    }
+
+    fn set_fade_with_time(
+        &mut self,
+        target_duty: Duty,
+        duration: Duration,
+    ) -> Result<Duty, EspError> {
+        self.check_running()?;
+        self.check_no_dither()?;
+        self.ensure_fade_func()?;
+        let target_duty = target_duty.min(self.max_duty);
+
+        esp!(unsafe {
+            ledc_set_fade_with_time(
+                self.timer().speed_mode,
+                C::channel(),
+                self.to_hw(target_duty),
+                fade_millis(duration),
+            )
+        })?;
+
+        Ok(target_duty)
+    }
+
+    fn start_fade(&mut self, mode: ledc_fade_mode_t) -> Result<(), EspError> {
+        esp!(unsafe { ledc_fade_start(self.timer().speed_mode, C::channel(), mode) })?;
+        Ok(())
+    }
+
+    /// Installs the fade function for channels created without
+    /// [`install_fade`](config::ChannelConfig::install_fade) and registers
+    /// the fade end callback
+    fn ensure_fade_func(&mut self) -> Result<(), EspError> {
+        if self.fade_func {
+            return Ok(());
+        }
+
+        acquire_fade_func()?;
+        #[cfg(any(esp_idf_version = "4.4", esp_idf_version_major = "5"))]
+        if let Err(err) = fade_end::register(self.timer().speed_mode, C::channel()) {
+            release_fade_func();
+            return Err(err);
+        }
+
+        FADE_CHANNELS[self.timer().speed_mode as usize]
+            .fetch_or(1 << C::channel(), Ordering::SeqCst);
+        self.fade_func = true;
+        Ok(())
+    }
+
+    /// Marks the channel as running, also after a halt by
+    /// [`Timer::stop_all_channels()`], and outputs `duty` with the high phase
+    /// starting at `hpoint`
+    fn start(&mut self, duty: Duty, hpoint: Hpoint) -> Result<(), EspError> {
+        let previous = self.state();
+        self.state = ChannelState::Running;
+        HALTED_CHANNELS[self.timer().speed_mode as usize]
+            .fetch_and(!(1 << C::channel()), Ordering::SeqCst);
+        if let Err(err) = self.update_duty(duty, hpoint) {
+            self.state = previous;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn check_running(&self) -> Result<(), EspError> {
+        if self.state() == ChannelState::Stopped {
+            return Err(EspError::from(ESP_ERR_INVALID_STATE as i32).unwrap());
+        }
+
+        Ok(())
+    }
+
+    /// Removes this channel from the registry of its timer and releases the
+    /// fade function if this channel is using it
+    fn unregister(&self) {
+        let speed_mode = self.timer().speed_mode;
+        bound_channels(speed_mode, H::timer()).fetch_and(!(1 << C::channel()), Ordering::SeqCst);
+        if self.fade_func {
+            FADE_CHANNELS[speed_mode as usize].fetch_and(!(1 << C::channel()), Ordering::SeqCst);
+            release_fade_func();
+        }
+    }
+
+    /// Validates that the high phase of `duty` starting at `hpoint` lies
+    /// within the timer period and returns the hpoint for passing it to the
+    /// SDK
+    fn check_duty_with_hpoint(&self, duty: Duty, hpoint: u32) -> Result<Hpoint, EspError> {
+        let hpoint = Hpoint::new(hpoint, self.timer().resolution)?;
+        if duty > self.max_duty || hpoint.get() + duty > self.max_duty + 1 {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        Ok(hpoint)
+    }
+
+    fn check_no_dither(&self) -> Result<(), EspError> {
+        if self.dither.is_some() {
+            return Err(EspError::from(ESP_ERR_INVALID_STATE as i32).unwrap());
+        }
+
+        Ok(())
+    }
+
+    /// Converts between logical and hardware duties, which is the same in
+    /// both directions
+    fn to_hw(&self, duty: Duty) -> Duty {
+        if self.inverted {
+            self.max_duty.saturating_sub(duty)
+        } else {
+            duty
+        }
+    }
+
+    /// Checks that the hardware has taken over `duty` when verification is
+    /// enabled and dithering is disabled
+    fn verify_duty(&self, duty: Duty) -> Result<(), EspError> {
+        if !self.verify || self.dither.is_some() {
+            return Ok(());
+        }
+
+        // The new duty latches with the next overflow of the timer. Allow
+        // for two periods until giving up.
+        let timeout = self.timer().get_period()?.as_micros() as i64 * 2;
+        let start = unsafe { esp_timer_get_time() };
+        while self.get_hw_duty() != duty {
+            if unsafe { esp_timer_get_time() } - start > timeout {
+                return Err(EspError::from(ESP_ERR_INVALID_STATE as i32).unwrap());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets and latches `duty` with the high phase starting at `hpoint`
+    ///
+    /// Returns `ESP_ERR_INVALID_STATE` for a stopped channel.
+    fn update_duty(&mut self, duty: Duty, hpoint: Hpoint) -> Result<(), EspError> {
+        self.check_running()?;
+        let speed_mode = self.timer().speed_mode;
+        let hw_duty = self.to_hw(duty);
+        if self.fade_func {
+            esp!(unsafe {
+                ledc_set_duty_and_update(speed_mode, C::channel(), hw_duty, hpoint.get())
+            })?;
+        } else {
+            // ledc_set_duty_and_update requires the fade function.
+            esp!(unsafe {
+                ledc_set_duty_with_hpoint(speed_mode, C::channel(), hw_duty, hpoint.get())
+            })?;
+            esp!(unsafe { ledc_update_duty(speed_mode, C::channel()) })?;
+        }
+        self.verify_duty(duty)?;
+        if let Some(dither) = &mut self.dither {
+            dither.set(duty, 0, hpoint.get())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// LED Control output channel owning its timer
+///
+/// Other than a [`Channel`] borrowing its timer, this has no lifetime and can
+/// be stored in long-lived driver structs without further ado. Create it by
+/// passing the timer by value to [`Channel::new()`] or with
+/// [`Channel::simple()`]. Use a borrowing channel for sharing a timer between
+/// multiple channels.
+pub type OwnedChannel<C, H, P, S = LowSpeed> = Channel<'static, C, H, P, S, Timer<H, S>>;
+
+impl<C: HwChannel, H: HwTimer + 'static, P: OutputPin> OwnedChannel<C, H, P> {
+    /// Creates a PWM output at `frequency` on `pin` with a channel owning its
+    /// timer
+    ///
+    /// The timer gets configured with the highest resolution achievable at
+    /// `frequency`. This covers the common case of a single output without
+    /// having to keep a borrowed timer around.
+    ///
+    /// ```
+    /// use embedded_hal::pwm::blocking::PwmPin;
+    /// use esp_idf_hal::ledc::Channel;
+    /// use esp_idf_hal::peripherals::Peripherals;
+    /// use esp_idf_hal::prelude::*;
+    ///
+    /// let peripherals = Peripherals::take().unwrap();
+    /// let mut channel = Channel::simple(
+    ///     peripherals.ledc.channel0,
+    ///     peripherals.ledc.timer0,
+    ///     peripherals.pins.gpio1,
+    ///     25.kHz().into(),
+    /// )?;
+    ///
+    /// channel.set_duty_percent(75)?;
+    /// ```
+    pub fn simple(channel: C, timer: H, pin: P, frequency: Hertz) -> Result<Self, LedcError> {
+        let config = config::TimerConfig::default()
+            .frequency(frequency)
+            .auto_resolution(true);
+        let timer = Timer::new(timer, &config)?;
+
+        Self::new(channel, timer, pin)
+    }
+}
+
+impl<C: HwChannel, H: HwTimer + 'static, P: OutputPin, S: HwSpeedMode + 'static>
+    OwnedChannel<C, H, P, S>
+{
+    /// Stops the output channel and its timer and releases the hardware
+    /// resources of both together with the GPIO pin
+    pub fn release_with_timer(mut self) -> Result<(C, H, P), EspError> {
+        self.stop(self.idle_level)?;
+        self.unregister();
+
+        // The channel has already been stopped and has released the fade
+        // function, so skip doing this once more when dropping it.
+        let this = ManuallyDrop::new(self);
+        // SAFETY: The fields are moved out exactly once and the channel itself
+        // won't be dropped.
+        let instance = unsafe { ptr::read(&this.instance) };
+        let pin = unsafe { ptr::read(&this.pin) };
+        let timer = unsafe { ptr::read(&this.timer) };
+        drop(unsafe { ptr::read(&this.dither) });
+
+        Ok((instance, timer.release()?, pin))
+    }
+
+    /// Changes only the resolution of the owned timer with
+    /// [`Timer::set_resolution()`] and rescales duty and hpoint of this
+    /// channel to the new resolution
+    ///
+    /// The visible output doesn't jump, as the duty keeps its ratio to the
+    /// period. Increasing the resolution gives more dimming steps, e.g. at
+    /// the low end of a LED's brightness. Returns `ESP_ERR_INVALID_ARG` if the
+    /// current frequency can't be generated at `resolution` and
+    /// `ESP_ERR_INVALID_STATE` while dithering is enabled.
+    pub fn set_timer_resolution(&mut self, resolution: config::Resolution) -> Result<(), EspError> {
+        let frequency = self.timer.get_frequency()?;
+        self.reconfigure_timer(frequency, resolution)
+    }
+
+    /// Changes frequency and resolution of the owned timer with
+    /// [`Timer::reconfigure()`] and rescales duty and hpoint of this channel
+    /// to the new resolution
+    ///
+    /// The duty keeps its ratio to the period, so switching between e.g. a
+    /// low frequency, high resolution profile for dimming and a high
+    /// frequency, low resolution one for driving a motor doesn't change the
+    /// average output. Returns `ESP_ERR_INVALID_STATE` while dithering is
+    /// enabled.
+    pub fn reconfigure_timer(
+        &mut self,
+        frequency: Hertz,
+        resolution: config::Resolution,
+    ) -> Result<(), EspError> {
+        self.check_no_dither()?;
+
+        let previous = self.timer.resolution;
+        self.timer.reconfigure(frequency, resolution)?;
+
+        let duty = rescale_duty(self.duty, previous, resolution);
+        let hpoint = Hpoint::new(
+            rescale_duty(self.hpoint.get(), previous, resolution),
+            resolution,
+        )
+        .unwrap_or_default();
+        self.max_duty = resolution.max_duty();
+        self.hpoint = hpoint;
+        self.duty = duty;
+
+        match self.state() {
+            ChannelState::Running => self.update_duty(duty, hpoint),
+            ChannelState::Stopped => Ok(()),
+        }
+    }
+}
+
+/// GPIO referenced only by its number for channels created with
+/// [`Channel::new_on_pin_number()`]
+///
+/// Other than the pin types from [`gpio`](crate::gpio), this doesn't prove
+/// ownership of the pin.
+pub struct PinNumber(i32);
+
+impl Pin for PinNumber {
+    type Error = EspError;
+
+    fn pin(&self) -> i32 {
+        self.0
+    }
+}
+
+impl OutputPin for PinNumber {}
+
+impl<'a, C: HwChannel, H: HwTimer, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    Channel<'a, C, H, PinNumber, S, T>
+{
+    /// Creates a new LED Control output channel abstraction driving the GPIO
+    /// with the number `gpio`
+    ///
+    /// This is meant for pins managed elsewhere, e.g. by a driver which has
+    /// claimed the pin and exposes it for PWM as well. The channel doesn't own
+    /// the pin in this case and the type system no longer prevents other code
+    /// from using it at the same time. Unsuitable GPIO numbers are rejected
+    /// by the SDK with [`LedcError::ChannelConfig`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has to ensure that nothing else drives the pin while the
+    /// channel is outputting to it, as already required for instantiating
+    /// pins with [`GpioPin::new()`](crate::gpio::GpioPin::new()).
+    pub unsafe fn new_on_pin_number(instance: C, timer: T, gpio: i32) -> Result<Self, LedcError> {
+        Self::new(instance, timer, PinNumber(gpio))
+    }
+
+    /// Creates a new LED Control output channel abstraction driving `pin`
+    /// which stays owned elsewhere
+    ///
+    /// This covers sharing a pin with a driver only lending it out, e.g. a
+    /// display driver owning the backlight pin. The channel only records the
+    /// pin's number, see [`new_on_pin_number()`](Channel::new_on_pin_number()).
+    ///
+    /// ```
+    /// // The display driver keeps owning the pin and only lends it out.
+    /// let backlight = display.backlight_pin();
+    /// let channel = unsafe { Channel::new_on_shared_pin(channel0, &timer, backlight)? };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The caller has to ensure that the owner of `pin` doesn't drive it
+    /// while the channel is outputting to it. The owner may reconfigure the
+    /// pin at any time and cut off the PWM output by doing so.
+    pub unsafe fn new_on_shared_pin<P: Pin>(
+        instance: C,
+        timer: T,
+        pin: &P,
+    ) -> Result<Self, LedcError> {
+        Self::new_on_pin_number(instance, timer, pin.pin())
+    }
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>> Drop
+    for Channel<'a, C, H, P, S, T>
+{
+    fn drop(&mut self) {
+        let _ = self.stop(self.idle_level);
+        self.unregister();
+    }
+}
+
+/// Prints the GPIO number instead of the pin itself
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    core::fmt::Debug for Channel<'a, C, H, P, S, T>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Channel")
+            .field("channel", &C::channel())
+            .field("timer", self.timer())
+            .field("gpio", &self.pin.pin())
+            .field("duty", &self.duty)
+            .field("max_duty", &self.max_duty)
+            .field("hpoint", &self.hpoint)
+            .field("idle_level", &self.idle_level)
+            .field("dithering", &self.dither.is_some())
+            .field("state", &self.state())
+            .field("inverted", &self.inverted)
+            .finish()
+    }
+}
+
+/// Prints a summary like `ch0 on timer0: 25000 Hz, 10-bit, duty 512/1023 (50.0%)`
+///
+/// This is meant for checking the setup at boot or from a status command.
+/// The frequency is read back from the hardware, the duty is the last one set
+/// (or the target of a fade).
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    core::fmt::Display for Channel<'a, C, H, P, S, T>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "ch{} on {}, duty {}/{} ({:.1}%)",
+            C::channel(),
+            self.timer(),
+            self.duty,
+            self.max_duty,
+            self.get_duty_fraction() * 100.0
+        )?;
+        if self.state() == ChannelState::Stopped {
+            write!(f, ", stopped")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Implements the PWM interface of embedded-hal 1.0.0-alpha.6 which all
+/// drivers of this crate are built on
+///
+/// `SetDutyCycle` from embedded-hal 1.0 is not provided. Cargo doesn't allow
+/// depending on both versions at the same time, so this requires moving the
+/// whole crate to embedded-hal 1.0.
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>> PwmPin
+    for Channel<'a, C, H, P, S, T>
+{
+    type Duty = Duty;
+    type Error = EspError;
+
+    /// Stops the output and drives the pin to the channel's idle level
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        self.stop(self.idle_level)
+    }
+
+    /// Restarts the output with the last duty set, also after it has been
+    /// stopped with [`Channel::stop()`]
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.start(self.duty, self.hpoint)
+    }
+
+    fn get_duty(&self) -> Result<Self::Duty, Self::Error> {
+        Ok(self.duty)
+    }
+
+    fn get_max_duty(&self) -> Result<Self::Duty, Self::Error> {
+        Ok(self.max_duty)
+    }
+
+    /// Sets the duty cycle of the channel
+    ///
+    /// Values beyond [`get_max_duty()`](PwmPin::get_max_duty()) saturate at
+    /// the maximum duty. Setting the duty the channel already has skips the
+    /// SDK calls, see [`Channel::force_update()`] for writing it regardless.
+    fn set_duty(&mut self, duty: Duty) -> Result<(), Self::Error> {
+        // Clamp the actual duty cycle to the current maximum as done by other
+        // Pwm/PwmPin implementations.
+        let clamped = duty.min(self.max_duty);
+        if clamped == self.duty && self.dither.is_none() {
+            return self.check_running();
+        }
+
+        self.force_update(clamped)
+    }
+}
+
+/// Duty updates which can be staged first and take effect later, as used by
+/// [`DutyGroup`]
+pub trait StagedDuty {
+    /// Stages `duty` (clamped to the maximum duty) without applying it
+    fn stage_duty(&mut self, duty: Duty) -> Result<(), EspError>;
+
+    /// Stages `duty` together with the position within the timer period
+    /// where the high phase begins without applying them
+    ///
+    /// This returns `ESP_ERR_NOT_SUPPORTED` unless implemented.
+    fn stage_duty_with_hpoint(&mut self, _duty: Duty, _hpoint: u32) -> Result<(), EspError> {
+        Err(EspError::from(ESP_ERR_NOT_SUPPORTED as i32).unwrap())
+    }
+
+    /// Applies the staged duty with the next PWM period
+    fn latch_duty(&mut self) -> Result<(), EspError>;
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>> StagedDuty
+    for Channel<'a, C, H, P, S, T>
+{
+    fn stage_duty(&mut self, duty: Duty) -> Result<(), EspError> {
+        self.set_duty_staged(duty)
+    }
+
+    fn stage_duty_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError> {
+        self.set_duty_with_hpoint_staged(duty, hpoint)
+    }
+
+    fn latch_duty(&mut self) -> Result<(), EspError> {
+        self.latch()
+    }
+}
+
+/// Group of channels whose duties are applied together
+///
+/// Updating channels one after another with
+/// [`set_duty()`](PwmPin::set_duty()) may let them take effect in different
+/// PWM periods, which shows up as shimmering when cross-fading colors of a
+/// RGB LED. A group stages the new duties first and latches them back to back
+/// with [`apply()`](DutyGroup::apply()). So channels sharing a timer switch
+/// with the same period, unless the timer overflows right between latching
+/// them.
+///
+/// ```
+/// use esp_idf_hal::ledc::{DutyGroup, StagedDuty};
+///
+/// let mut channels: [&mut dyn StagedDuty; 3] = [&mut red, &mut green, &mut blue];
+/// let mut group = DutyGroup::new(&mut channels);
+/// group.set_duty(0, 255)?;
+/// group.set_duty(1, 128)?;
+/// group.set_duty(2, 0)?;
+/// group.apply()?;
+/// ```
+pub struct DutyGroup<'g, 'c> {
+    channels: &'g mut [&'c mut dyn StagedDuty],
+}
+
+impl<'g, 'c> DutyGroup<'g, 'c> {
+    pub fn new(channels: &'g mut [&'c mut dyn StagedDuty]) -> Self {
+        Self { channels }
+    }
+
+    /// Stages the duty of the channel at `index` without applying it yet
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` for an index outside the group.
+    pub fn set_duty(&mut self, index: usize, duty: Duty) -> Result<(), EspError> {
+        self.channels
+            .get_mut(index)
+            .ok_or_else(|| EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap())?
+            .stage_duty(duty)
+    }
+
+    /// Stages duty and hpoint of the channel at `index` without applying them
+    /// yet, e.g. for shifting the phases of several channels in the same PWM
+    /// period
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` for an index outside the group.
+    pub fn set_duty_with_hpoint(
+        &mut self,
+        index: usize,
+        duty: Duty,
+        hpoint: u32,
+    ) -> Result<(), EspError> {
+        self.channels
+            .get_mut(index)
+            .ok_or_else(|| EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap())?
+            .stage_duty_with_hpoint(duty, hpoint)
+    }
+
+    /// Applies the staged duties of all channels in the group
+    pub fn apply(&mut self) -> Result<(), EspError> {
+        for channel in self.channels.iter_mut() {
+            channel.latch_duty()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adapter presenting a PWM output with a 16 bit duty scale as returned by
+/// [`Channel::as_u16_pwm()`]
+///
+/// Some drivers assume that [`get_max_duty()`](PwmPin::get_max_duty())
+/// returns `u16::MAX`. This adapter reports exactly that and scales duties
+/// from and to the actual resolution of the wrapped output, rounding to the
+/// nearest value. At resolutions below 16 bits, several `u16` duties map to
+/// the same hardware duty and reading back the duty returns the scaled
+/// hardware one.
+pub struct U16Pwm<'p, P: PwmPin<Duty = Duty>> {
+    pwm: &'p mut P,
+}
+
+impl<'p, P: PwmPin<Duty = Duty>> U16Pwm<'p, P> {
+    pub fn new(pwm: &'p mut P) -> Self {
+        Self { pwm }
+    }
+
+    fn scale(value: u32, from: u32, to: u32) -> u32 {
+        if from == 0 {
+            return 0;
+        }
+
+        ((value as u64 * to as u64 + from as u64 / 2) / from as u64) as u32
+    }
+}
+
+impl<'p, P: PwmPin<Duty = Duty>> PwmPin for U16Pwm<'p, P> {
+    type Duty = u16;
+    type Error = P::Error;
+
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        self.pwm.disable()
+    }
+
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.pwm.enable()
+    }
+
+    fn get_duty(&self) -> Result<Self::Duty, Self::Error> {
+        let max_duty = self.pwm.get_max_duty()?;
+        let duty = self.pwm.get_duty()?.min(max_duty);
+        Ok(Self::scale(duty, max_duty, u16::MAX as u32) as u16)
+    }
+
+    fn get_max_duty(&self) -> Result<Self::Duty, Self::Error> {
+        Ok(u16::MAX)
+    }
+
+    fn set_duty(&mut self, duty: Self::Duty) -> Result<(), Self::Error> {
+        let max_duty = self.pwm.get_max_duty()?;
+        self.pwm
+            .set_duty(Self::scale(duty as u32, u16::MAX as u32, max_duty))
+    }
+}
+
+mod chip {
+    use core::convert::TryFrom;
+    use core::fmt;
+    use core::marker::PhantomData;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use esp_idf_sys::*;
+
+    /// Duty cycle resolution of a LED Control timer
+    ///
+    /// Resolutions above 14 bits are only supported by the ESP32.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Resolution {
+        Bits1,
+        Bits2,
+        Bits3,
+        Bits4,
+        Bits5,
+        Bits6,
+        Bits7,
+        Bits8,
+        Bits9,
+        Bits10,
+        Bits11,
+        Bits12,
+        Bits13,
+        Bits14,
+        #[cfg(esp32)]
+        Bits15,
+        #[cfg(esp32)]
+        Bits16,
+        #[cfg(esp32)]
+        Bits17,
+        #[cfg(esp32)]
+        Bits18,
+        #[cfg(esp32)]
+        Bits19,
+        #[cfg(esp32)]
+        Bits20,
+    }
+
+    impl Resolution {
+        /// Highest resolution supported by the chip built for. Higher
+        /// resolutions are not available as variants at all, so they can't
+        /// be passed to the SDK by accident.
+        #[cfg(esp32)]
+        pub const HIGHEST: Resolution = Resolution::Bits20;
+        /// Highest resolution supported by the chip built for. Higher
+        /// resolutions are not available as variants at all, so they can't
+        /// be passed to the SDK by accident.
+        #[cfg(not(esp32))]
+        pub const HIGHEST: Resolution = Resolution::Bits14;
+
+        /// Returns the highest resolution at which `frequency` can be
+        /// generated from a source clock running at `source_frequency` or
+        /// `None` if not even a 1 bit resolution is possible
+        pub(crate) fn highest_for(frequency: u32, source_frequency: u32) -> Option<Self> {
+            let counts = source_frequency.checked_div(frequency)?;
+            if counts < 2 {
+                return None;
+            }
+
+            let bits = (31 - counts.leading_zeros() as u8).min(Self::HIGHEST.bits());
+            Self::from_bits(bits)
+        }
+
+        pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+            match bits {
+                1 => Some(Resolution::Bits1),
+                2 => Some(Resolution::Bits2),
+                3 => Some(Resolution::Bits3),
+                4 => Some(Resolution::Bits4),
+                5 => Some(Resolution::Bits5),
+                6 => Some(Resolution::Bits6),
+                7 => Some(Resolution::Bits7),
+                8 => Some(Resolution::Bits8),
+                9 => Some(Resolution::Bits9),
+                10 => Some(Resolution::Bits10),
+                11 => Some(Resolution::Bits11),
+                12 => Some(Resolution::Bits12),
+                13 => Some(Resolution::Bits13),
+                14 => Some(Resolution::Bits14),
+                #[cfg(esp32)]
+                15 => Some(Resolution::Bits15),
+                #[cfg(esp32)]
+                16 => Some(Resolution::Bits16),
+                #[cfg(esp32)]
+                17 => Some(Resolution::Bits17),
+                #[cfg(esp32)]
+                18 => Some(Resolution::Bits18),
+                #[cfg(esp32)]
+                19 => Some(Resolution::Bits19),
+                #[cfg(esp32)]
+                20 => Some(Resolution::Bits20),
+                _ => None,
+            }
+        }
+
+        /// Returns the number of bits of this resolution
+        pub const fn bits(&self) -> u8 {
+            self.timer_bits() as u8
+        }
+
+        /// Returns the maximum duty value at this resolution
+        pub const fn max_duty(&self) -> u32 {
+            (1 << self.bits()) - 1
+        }
+
+        /// Returns the number of timer counts per period at this resolution
+        pub const fn counts(&self) -> u32 {
+            1 << self.bits()
+        }
+
+        pub(crate) const fn timer_bits(&self) -> ledc_timer_bit_t {
+            match self {
+                Resolution::Bits1 => ledc_timer_bit_t_LEDC_TIMER_1_BIT,
+                Resolution::Bits2 => ledc_timer_bit_t_LEDC_TIMER_2_BIT,
+                Resolution::Bits3 => ledc_timer_bit_t_LEDC_TIMER_3_BIT,
+                Resolution::Bits4 => ledc_timer_bit_t_LEDC_TIMER_4_BIT,
+                Resolution::Bits5 => ledc_timer_bit_t_LEDC_TIMER_5_BIT,
+                Resolution::Bits6 => ledc_timer_bit_t_LEDC_TIMER_6_BIT,
+                Resolution::Bits7 => ledc_timer_bit_t_LEDC_TIMER_7_BIT,
+                Resolution::Bits8 => ledc_timer_bit_t_LEDC_TIMER_8_BIT,
+                Resolution::Bits9 => ledc_timer_bit_t_LEDC_TIMER_9_BIT,
+                Resolution::Bits10 => ledc_timer_bit_t_LEDC_TIMER_10_BIT,
+                Resolution::Bits11 => ledc_timer_bit_t_LEDC_TIMER_11_BIT,
+                Resolution::Bits12 => ledc_timer_bit_t_LEDC_TIMER_12_BIT,
+                Resolution::Bits13 => ledc_timer_bit_t_LEDC_TIMER_13_BIT,
+                Resolution::Bits14 => ledc_timer_bit_t_LEDC_TIMER_14_BIT,
+                #[cfg(esp32)]
+                Resolution::Bits15 => ledc_timer_bit_t_LEDC_TIMER_15_BIT,
+                #[cfg(esp32)]
+                Resolution::Bits16 => ledc_timer_bit_t_LEDC_TIMER_16_BIT,
+                #[cfg(esp32)]
+                Resolution::Bits17 => ledc_timer_bit_t_LEDC_TIMER_17_BIT,
+                #[cfg(esp32)]
+                Resolution::Bits18 => ledc_timer_bit_t_LEDC_TIMER_18_BIT,
+                #[cfg(esp32)]
+                Resolution::Bits19 => ledc_timer_bit_t_LEDC_TIMER_19_BIT,
+                #[cfg(esp32)]
+                Resolution::Bits20 => ledc_timer_bit_t_LEDC_TIMER_20_BIT,
+            }
+        }
+    }
+
+    /// Error of converting a number of bits not supported by the chip built
+    /// for into a [`Resolution`]
+    #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+    pub struct InvalidResolutionError(pub u8);
+
+    impl fmt::Display for InvalidResolutionError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                f,
+                "invalid resolution of {} bits (supported: 1 to {})",
+                self.0,
+                Resolution::HIGHEST.bits()
+            )
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for InvalidResolutionError {}
+
+    /// Converts a number of bits, failing for zero and for more bits than
+    /// [`Resolution::HIGHEST`]
+    impl TryFrom<u8> for Resolution {
+        type Error = InvalidResolutionError;
+
+        fn try_from(bits: u8) -> Result<Self, Self::Error> {
+            Self::from_bits(bits).ok_or(InvalidResolutionError(bits))
+        }
+    }
+
+    impl From<Resolution> for u8 {
+        fn from(resolution: Resolution) -> Self {
+            resolution.bits()
+        }
+    }
+
+    /// LED Control peripheral timer
+    pub trait HwTimer {
+        /// Highest duty resolution in bits supported by the timers of the
+        /// chip built for: 20 on the ESP32 and 14 on the ESP32-S2, ESP32-S3
+        /// and ESP32-C3
+        ///
+        /// This is the number of bits of [`Resolution::HIGHEST`] and allows
+        /// generic code to clamp a requested resolution without chip-specific
+        /// branches.
+        const MAX_RESOLUTION_BITS: u8 = Resolution::HIGHEST.bits();
+
+        fn timer() -> ledc_timer_t;
+    }
+
+    /// LED Control peripheral output channel
+    pub trait HwChannel {
+        fn channel() -> ledc_channel_t;
+    }
+
+    /// Speed mode of LED Control timers and channels at the type level
+    pub trait HwSpeedMode {
+        fn speed_mode() -> ledc_mode_t;
+    }
+
+    /// Low speed mode, available on all chips
+    #[derive(Copy, Clone, Debug)]
+    pub struct LowSpeed;
+
+    impl HwSpeedMode for LowSpeed {
+        fn speed_mode() -> ledc_mode_t {
+            ledc_mode_t_LEDC_LOW_SPEED_MODE
+        }
+    }
+
+    /// High speed mode, only available on the ESP32
+    ///
+    /// Updates duty and frequency immediately by hardware instead of at the
+    /// next overflow of the timer.
+    #[cfg(esp32)]
+    #[derive(Copy, Clone, Debug)]
+    pub struct HighSpeed;
+
+    #[cfg(esp32)]
+    impl HwSpeedMode for HighSpeed {
+        fn speed_mode() -> ledc_mode_t {
+            ledc_mode_t_LEDC_HIGH_SPEED_MODE
+        }
+    }
+
+    /// GPIOs usable as LED Control outputs on the chip built for
+    ///
+    /// This excludes input-only GPIOs and the ones connected to the SPI flash
+    /// (and PSRAM) of typical modules. Some of the listed GPIOs are strapping
+    /// pins, see [`STRAPPING_PINS`].
+    #[cfg(esp32)]
+    pub const VALID_OUTPUT_PINS: &[i32] = &[
+        0, 1, 2, 3, 4, 5, 12, 13, 14, 15, 16, 17, 18, 19, 21, 22, 23, 25, 26, 27, 32, 33,
+    ];
+    /// GPIOs usable as LED Control outputs on the chip built for
+    ///
+    /// This excludes input-only GPIOs and the ones connected to the SPI flash
+    /// (and PSRAM) of typical modules. Some of the listed GPIOs are strapping
+    /// pins, see [`STRAPPING_PINS`].
+    #[cfg(esp32s2)]
+    pub const VALID_OUTPUT_PINS: &[i32] = &[
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 33, 34, 35,
+        36, 37, 38, 39, 40, 41, 42, 43, 44, 45,
+    ];
+    /// GPIOs usable as LED Control outputs on the chip built for
+    ///
+    /// This excludes the GPIOs connected to the SPI flash (and PSRAM) of
+    /// typical modules. GPIOs 33 to 37 are used by octal flash or PSRAM as
+    /// well. Some of the listed GPIOs are strapping pins, see
+    /// [`STRAPPING_PINS`].
+    #[cfg(esp32s3)]
+    pub const VALID_OUTPUT_PINS: &[i32] = &[
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 33, 34, 35,
+        36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48,
+    ];
+    /// GPIOs usable as LED Control outputs on the chip built for
+    ///
+    /// This excludes the GPIOs connected to the SPI flash of typical modules.
+    /// Some of the listed GPIOs are strapping pins, see [`STRAPPING_PINS`].
+    #[cfg(esp32c3)]
+    pub const VALID_OUTPUT_PINS: &[i32] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 18, 19, 20, 21];
+
+    /// Strapping pins of the chip built for. Their level at reset selects the
+    /// boot mode, so external circuitry driven by a PWM output must not pull
+    /// them the wrong way.
+    #[cfg(esp32)]
+    pub const STRAPPING_PINS: &[i32] = &[0, 2, 5, 12, 15];
+    /// Strapping pins of the chip built for. Their level at reset selects the
+    /// boot mode, so external circuitry driven by a PWM output must not pull
+    /// them the wrong way.
+    #[cfg(esp32s2)]
+    pub const STRAPPING_PINS: &[i32] = &[0, 45, 46];
+    /// Strapping pins of the chip built for. Their level at reset selects the
+    /// boot mode, so external circuitry driven by a PWM output must not pull
+    /// them the wrong way.
+    #[cfg(esp32s3)]
+    pub const STRAPPING_PINS: &[i32] = &[0, 3, 45, 46];
+    /// Strapping pins of the chip built for. Their level at reset selects the
+    /// boot mode, so external circuitry driven by a PWM output must not pull
+    /// them the wrong way.
+    #[cfg(esp32c3)]
+    pub const STRAPPING_PINS: &[i32] = &[2, 8, 9];
+
+    /// Returns whether `gpio` is contained in [`VALID_OUTPUT_PINS`]
+    pub const fn is_valid_output_pin(gpio: i32) -> bool {
+        let mut index = 0;
+        while index < VALID_OUTPUT_PINS.len() {
+            if VALID_OUTPUT_PINS[index] == gpio {
+                return true;
+            }
+            index += 1;
+        }
+
+        false
+    }
+
+    macro_rules! impl_timer {
+        ($instance:ident: $timer:expr) => {
+            pub struct $instance(PhantomData<*const ()>);
+
+            impl $instance {
+                /// # Safety
+                ///
+                /// It is safe to instantiate this timer exactly one time.
+                pub unsafe fn new() -> Self {
+                    $instance(PhantomData)
+                }
+            }
+
+            impl core::fmt::Debug for $instance {
+                fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    f.write_str(stringify!($instance))
+                }
+            }
+
+            // SAFETY: The timer is only a token for the hardware timer, which
+            // can be configured from any task or core.
+            unsafe impl Send for $instance {}
+
+            impl HwTimer for $instance {
+                fn timer() -> ledc_timer_t {
+                    $timer
+                }
+            }
+        };
+    }
+
+    impl_timer!(TIMER0: ledc_timer_t_LEDC_TIMER_0);
+    impl_timer!(TIMER1: ledc_timer_t_LEDC_TIMER_1);
+    impl_timer!(TIMER2: ledc_timer_t_LEDC_TIMER_2);
+    impl_timer!(TIMER3: ledc_timer_t_LEDC_TIMER_3);
+
+    macro_rules! impl_channel {
+        ($instance:ident: $channel:expr) => {
+            pub struct $instance(PhantomData<*const ()>);
+
+            impl $instance {
+                /// # Safety
+                ///
+                /// It is safe to instantiate this output channel exactly one
+                /// time.
+                pub unsafe fn new() -> Self {
+                    $instance(PhantomData)
+                }
+            }
+
+            impl core::fmt::Debug for $instance {
+                fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                    f.write_str(stringify!($instance))
+                }
+            }
+
+            // SAFETY: The channel is only a token for the hardware channel,
+            // which can be configured from any task or core.
+            unsafe impl Send for $instance {}
+
+            impl HwChannel for $instance {
+                fn channel() -> ledc_channel_t {
+                    $channel
+                }
+            }
+        };
+    }
+
+    impl_channel!(CHANNEL0: ledc_channel_t_LEDC_CHANNEL_0);
+    impl_channel!(CHANNEL1: ledc_channel_t_LEDC_CHANNEL_1);
+    impl_channel!(CHANNEL2: ledc_channel_t_LEDC_CHANNEL_2);
+    impl_channel!(CHANNEL3: ledc_channel_t_LEDC_CHANNEL_3);
+    impl_channel!(CHANNEL4: ledc_channel_t_LEDC_CHANNEL_4);
+    impl_channel!(CHANNEL5: ledc_channel_t_LEDC_CHANNEL_5);
+    // The ESP32-C3 only has six channels. Not defining the remaining ones
+    // turns using them into a compile error instead of a runtime failure.
+    #[cfg(any(esp32, esp32s2, esp32s3))]
+    impl_channel!(CHANNEL6: ledc_channel_t_LEDC_CHANNEL_6);
+    #[cfg(any(esp32, esp32s2, esp32s3))]
+    impl_channel!(CHANNEL7: ledc_channel_t_LEDC_CHANNEL_7);
+
+    /// The LED Control device peripheral
+    ///
+    /// It provides the four timers and the output channels supported by the
+    /// chip: eight on the ESP32, ESP32-S2 and ESP32-S3 and six on the
+    /// ESP32-C3.
+    pub struct Peripheral {
+        pub timer0: TIMER0,
+        pub timer1: TIMER1,
+        pub timer2: TIMER2,
+        pub timer3: TIMER3,
+        pub channel0: CHANNEL0,
+        pub channel1: CHANNEL1,
+        pub channel2: CHANNEL2,
+        pub channel3: CHANNEL3,
+        pub channel4: CHANNEL4,
+        pub channel5: CHANNEL5,
+        #[cfg(any(esp32, esp32s2, esp32s3))]
+        pub channel6: CHANNEL6,
+        #[cfg(any(esp32, esp32s2, esp32s3))]
+        pub channel7: CHANNEL7,
+    }
+
+    /// Whether the LEDC peripheral has been handed out by
+    /// [`Peripheral::take()`], [`Peripheral::new()`] or as part of
+    /// [`Peripherals::take()`](crate::peripherals::Peripherals::take())
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    impl Peripheral {
+        /// Takes the LEDC peripheral on its own
+        ///
+        /// This is intended for libraries and applications which only need
+        /// LED Control and don't want to take the whole
+        /// [`Peripherals`](crate::peripherals::Peripherals). Returns `None` if
+        /// the LEDC peripheral has already been taken, either by a previous
+        /// call or as part of
+        /// [`Peripherals::take()`](crate::peripherals::Peripherals::take()).
+        ///
+        /// Both share the same singleton state, so the device peripherals
+        /// can't be taken anymore once the LEDC peripheral has been taken here.
+        /// Libraries preferably accept the timers and channels they need as
+        /// arguments and leave acquiring them to the application.
+        pub fn take() -> Option<Self> {
+            if Self::claim() {
+                Some(unsafe { Self::steal() })
+            } else {
+                None
+            }
+        }
+
+        /// Marks the LEDC peripheral as taken and returns whether it has been
+        /// available before
+        pub(crate) fn claim() -> bool {
+            !TAKEN.swap(true, Ordering::SeqCst)
+        }
+
+        /// Creates a new instance of the LEDC peripheral. Typically one wants
+        /// to use the instance [`ledc`](crate::peripherals::Peripherals::ledc) from
+        /// the device peripherals obtained via
+        /// [`peripherals::Peripherals::take()`](crate::peripherals::Peripherals::take()).
+        ///
+        /// This marks the LEDC peripheral as taken, so a subsequent
+        /// [`Peripheral::take()`] returns `None`.
+        ///
+        /// # Safety
+        ///
+        /// It is safe to instantiate the LEDC peripheral exactly one time.
+        /// Care has to be taken that this has not already been done elsewhere.
+        pub unsafe fn new() -> Self {
+            TAKEN.store(true, Ordering::SeqCst);
+            Self::steal()
+        }
+
+        /// Creates an instance of the LEDC peripheral regardless of whether it
+        /// has already been taken and without marking it as taken
+        ///
+        /// This mirrors `steal()` of other HAL crates and is meant for cases
+        /// like panic handlers which need to force outputs into a safe state.
+        ///
+        /// # Safety
+        ///
+        /// The timers and channels of the returned instance alias any other
+        /// instance in existence. The caller has to ensure that at most one
+        /// instance gets used for configuring and driving them at a time.
+        pub unsafe fn steal() -> Self {
+            Self {
+                timer0: TIMER0::new(),
+                timer1: TIMER1::new(),
+                timer2: TIMER2::new(),
+                timer3: TIMER3::new(),
+                channel0: CHANNEL0::new(),
+                channel1: CHANNEL1::new(),
+                channel2: CHANNEL2::new(),
+                channel3: CHANNEL3::new(),
+                channel4: CHANNEL4::new(),
+                channel5: CHANNEL5::new(),
+                #[cfg(any(esp32, esp32s2, esp32s3))]
+                channel6: CHANNEL6::new(),
+                #[cfg(any(esp32, esp32s2, esp32s3))]
+                channel7: CHANNEL7::new(),
+            }
+        }
+    }
+
+    /// Evaluates `$body` once for every output channel of the LED Control
+    /// peripheral `$ledc`, with `$channel` bound to the respective channel
+    ///
+    /// The channels have distinct types, so this expands to one copy of
+    /// `$body` per channel instead of looping at runtime. Only the channels
+    /// available on the chip built for are visited. `$ledc` has to be a place
+    /// expression like `peripherals.ledc` as the channels are moved out of it
+    /// one by one, which leaves its timers available.
+    ///
+    /// This is intended for bring-up and test code which treats all channels
+    /// the same. For example to drive a 50 % duty cycle on GPIOs 1 and up
+    /// ```
+    /// use embedded_hal::pwm::blocking::PwmPin;
+    /// use esp_idf_hal::ledc::{config::TimerConfig, Channel, Timer};
+    /// use esp_idf_hal::ledc_for_each_channel;
+    /// use esp_idf_hal::peripherals::Peripherals;
+    /// use esp_idf_sys::EspError;
+    ///
+    /// let peripherals = Peripherals::take().unwrap();
+    /// let timer = Timer::new(peripherals.ledc.timer0, &TimerConfig::default())?;
+    /// let mut channels: Vec<Box<dyn PwmPin<Duty = u32, Error = EspError>>> = Vec::new();
+    /// let mut gpio = 1;
+    ///
+    /// ledc_for_each_channel!(peripherals.ledc, |channel| {
+    ///     let mut channel = unsafe { Channel::new_on_pin_number(channel, &timer, gpio)? };
+    ///     channel.set_duty(channel.get_max_duty()? / 2)?;
+    ///     channels.push(Box::new(channel));
+    ///     gpio += 1;
+    /// });
+    /// ```
+    #[cfg(any(esp32, esp32s2, esp32s3))]
+    #[macro_export]
+    macro_rules! ledc_for_each_channel {
+        ($ledc:expr, |$channel:ident| $body:expr) => {{
+            {
+                let $channel = $ledc.channel0;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel1;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel2;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel3;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel4;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel5;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel6;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel7;
+                $body;
+            }
+        }};
+    }
+
+    /// Evaluates `$body` once for every output channel of the LED Control
+    /// peripheral `$ledc`, with `$channel` bound to the respective channel
+    ///
+    /// See the documentation for chips with eight channels for details. The
+    /// ESP32-C3 only has six of them.
+    #[cfg(esp32c3)]
+    #[macro_export]
+    macro_rules! ledc_for_each_channel {
+        ($ledc:expr, |$channel:ident| $body:expr) => {{
+            {
+                let $channel = $ledc.channel0;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel1;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel2;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel3;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel4;
+                $body;
+            }
+            {
+                let $channel = $ledc.channel5;
+                $body;
+            }
+        }};
+    }
+}
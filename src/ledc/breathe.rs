@@ -0,0 +1,132 @@
+//! Breathing LED effect on top of a LED Control channel
+
+use core::borrow::Borrow;
+use core::time::Duration;
+
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+use super::{Channel, Duty, HwChannel, HwSpeedMode, HwTimer, LowSpeed, Timer};
+use crate::gpio::OutputPin;
+
+/// Continuously fades a channel up and down between two duties
+///
+/// The fades are carried out by the hardware. After [`start()`](Breathe::start()),
+/// [`tick()`](Breathe::tick()) has to be called regularly for reversing the
+/// direction at the end of each fade. This doesn't block and can be done from
+/// the main loop of an application. Alternatively,
+/// [`run()`](Breathe::run()) keeps breathing forever in the calling task.
+///
+/// ```
+/// use core::time::Duration;
+/// use esp_idf_hal::ledc::{breathe::Breathe, config::TimerConfig, Channel, Timer};
+/// use esp_idf_hal::peripherals::Peripherals;
+/// use esp_idf_hal::prelude::*;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let config = TimerConfig::default().frequency(5.kHz().into());
+/// let timer = Timer::new(peripherals.ledc.timer0, &config)?;
+/// let channel = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+/// let mut breathe = Breathe::new(channel, Duration::from_secs(4), 0, 255)?;
+///
+/// breathe.start()?;
+/// loop {
+///     breathe.tick()?;
+///     // Do other things
+/// }
+/// ```
+pub struct Breathe<
+    'a,
+    C: HwChannel,
+    H: HwTimer,
+    P: OutputPin,
+    S: HwSpeedMode = LowSpeed,
+    T = &'a Timer<H, S>,
+> where
+    T: Borrow<Timer<H, S>>,
+{
+    channel: Channel<'a, C, H, P, S, T>,
+    half_period: Duration,
+    min_duty: Duty,
+    max_duty: Duty,
+    target: Option<Duty>,
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    Breathe<'a, C, H, P, S, T>
+{
+    /// Creates a breathing effect from a channel
+    ///
+    /// One `period` consists of fading from `min_duty` to `max_duty` and
+    /// back. Returns `ESP_ERR_INVALID_ARG` if `min_duty` exceeds `max_duty` or
+    /// `max_duty` exceeds the maximum duty of the channel.
+    pub fn new(
+        channel: Channel<'a, C, H, P, S, T>,
+        period: Duration,
+        min_duty: Duty,
+        max_duty: Duty,
+    ) -> Result<Self, EspError> {
+        if min_duty > max_duty || max_duty > channel.max_duty {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        Ok(Self {
+            channel,
+            half_period: period / 2,
+            min_duty,
+            max_duty,
+            target: None,
+        })
+    }
+
+    /// Starts breathing by fading up from the minimum duty
+    pub fn start(&mut self) -> Result<(), EspError> {
+        self.channel.set_duty(self.min_duty)?;
+        self.fade_to(self.max_duty)
+    }
+
+    /// Reverses the direction once the current fade is done
+    ///
+    /// Does nothing if the fade is still in progress or breathing has not
+    /// been started.
+    pub fn tick(&mut self) -> Result<(), EspError> {
+        match self.target {
+            Some(target) if self.channel.get_hw_duty() == target => {
+                self.fade_to(self.reverse(target))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Keeps breathing forever and blocks the calling task while the
+    /// hardware fades. Only returns in case of an error.
+    pub fn run(&mut self) -> Result<(), EspError> {
+        self.channel.set_duty(self.min_duty)?;
+
+        let mut target = self.max_duty;
+        loop {
+            self.channel
+                .fade_with_time_blocking(target, self.half_period, None)?;
+            target = self.reverse(target);
+        }
+    }
+
+    /// Releases the underlying channel. A fade in progress runs to its end.
+    pub fn release(self) -> Channel<'a, C, H, P, S, T> {
+        self.channel
+    }
+
+    fn reverse(&self, target: Duty) -> Duty {
+        if target == self.max_duty {
+            self.min_duty
+        } else {
+            self.max_duty
+        }
+    }
+
+    fn fade_to(&mut self, target: Duty) -> Result<(), EspError> {
+        self.channel.fade_with_time(target, self.half_period)?;
+        self.target = Some(target);
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+//! H-bridge motor drive on top of two LED Control channels
+
+use core::borrow::Borrow;
+
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+use super::{Channel, ChannelState, Duty, HwChannel, HwSpeedMode, HwTimer, Timer};
+use crate::gpio::{Level, OutputPin};
+
+/// A leg of a [`HBridge`]
+///
+/// This is implemented for [`Channel`] and provides the phase control and
+/// static output levels required for complementary drive.
+pub trait BridgeChannel: PwmPin<Duty = Duty, Error = EspError> {
+    /// Sets the duty cycle and the position within the timer period where
+    /// the high phase begins, see [`Channel::set_duty_with_hpoint()`]
+    fn set_duty_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError>;
+
+    /// Restarts the output with a new duty and hpoint instead of the last
+    /// ones, see [`Channel::restart_with_hpoint()`]
+    fn restart_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError>;
+
+    /// Stops the output and drives the pin statically at `level`, see
+    /// [`Channel::stop()`]
+    fn stop(&mut self, level: Level) -> Result<(), EspError>;
+
+    /// Returns whether the channel is running or has been stopped
+    fn state(&self) -> ChannelState;
+
+    /// Returns the index of the hardware timer the channel is bound to
+    fn timer_index(&self) -> ledc_timer_t;
+
+    /// Returns the speed mode of the hardware timer the channel is bound to
+    ///
+    /// Timer indices are only unique within a speed mode, e.g. the ESP32 has
+    /// a low and a high speed timer 0.
+    fn speed_mode(&self) -> ledc_mode_t;
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    BridgeChannel for Channel<'a, C, H, P, S, T>
+{
+    fn set_duty_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError> {
+        Channel::set_duty_with_hpoint(self, duty, hpoint)
+    }
+
+    fn restart_with_hpoint(&mut self, duty: Duty, hpoint: u32) -> Result<(), EspError> {
+        Channel::restart_with_hpoint(self, duty, hpoint)
+    }
+
+    fn stop(&mut self, level: Level) -> Result<(), EspError> {
+        Channel::stop(self, level)
+    }
+
+    fn state(&self) -> ChannelState {
+        Channel::state(self)
+    }
+
+    fn timer_index(&self) -> ledc_timer_t {
+        Channel::timer_index(self)
+    }
+
+    fn speed_mode(&self) -> ledc_mode_t {
+        S::speed_mode()
+    }
+}
+
+/// Drives a DC motor through an H-bridge from two complementary channels
+///
+/// The signed drive maps to locked anti-phase PWM: leg A is high for the
+/// first part of each timer period and leg B for the rest, so a drive of
+/// `0.0` results in a zero average voltage, `1.0` in full forward and `-1.0`
+/// in full reverse.
+///
+/// Both legs are held low for `dead_time` timer counts around each edge to
+/// avoid shoot-through while the bridge switches over. This requires both
+/// channels to be bound to the same timer for keeping their phases locked.
+///
+/// ```
+/// use esp_idf_hal::ledc::{config::TimerConfig, hbridge::HBridge, Channel, Timer};
+/// use esp_idf_hal::peripherals::Peripherals;
+/// use esp_idf_hal::prelude::*;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let config = TimerConfig::default().frequency(20.kHz().into());
+/// let timer = Timer::new(peripherals.ledc.timer0, &config)?;
+/// let a = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+/// let b = Channel::new(peripherals.ledc.channel1, &timer, peripherals.pins.gpio2)?;
+/// let mut bridge = HBridge::new(a, b, 4)?;
+///
+/// bridge.set_drive(0.5)?;
+/// bridge.set_drive(-0.25)?;
+/// bridge.brake()?;
+/// ```
+pub struct HBridge<A: BridgeChannel, B: BridgeChannel> {
+    a: A,
+    b: B,
+    dead_time: u32,
+    drive: f32,
+}
+
+impl<A: BridgeChannel, B: BridgeChannel> HBridge<A, B> {
+    /// Creates a H-bridge drive from its two legs which coasts initially
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` if the channels are bound to different
+    /// timers or `dead_time` leaves no room for driving within a timer
+    /// period.
+    pub fn new(a: A, b: B, dead_time: u32) -> Result<Self, EspError> {
+        if a.speed_mode() != b.speed_mode() || a.timer_index() != b.timer_index() {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        let mut bridge = Self {
+            a,
+            b,
+            dead_time: 0,
+            drive: 0.0,
+        };
+        bridge.set_dead_time(dead_time)?;
+        bridge.coast()?;
+
+        Ok(bridge)
+    }
+
+    /// Sets the time both legs are held low around each edge in timer counts
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` if twice the dead time exceeds the timer
+    /// period. The new dead time takes effect with the next
+    /// [`set_drive()`](HBridge::set_drive()).
+    pub fn set_dead_time(&mut self, dead_time: u32) -> Result<(), EspError> {
+        if 2 * dead_time as u64 > self.period()? as u64 {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        self.dead_time = dead_time;
+        Ok(())
+    }
+
+    /// Returns the dead time in timer counts
+    pub fn dead_time(&self) -> u32 {
+        self.dead_time
+    }
+
+    /// Sets the signed drive within `-1.0..=1.0`
+    ///
+    /// Positive values drive forward and negative ones reverse. Values out of
+    /// range get clamped and NaN is treated as `0.0`. This restarts the legs
+    /// after [`brake()`](HBridge::brake()) or [`coast()`](HBridge::coast()).
+    pub fn set_drive(&mut self, drive: f32) -> Result<(), EspError> {
+        let drive = if drive.is_nan() {
+            0.0
+        } else {
+            drive.clamp(-1.0, 1.0)
+        };
+
+        let max_duty = self.a.get_max_duty()?;
+        let available = self.period()? - 2 * self.dead_time;
+        let high_a = ((1.0 + drive) / 2.0 * available as f32 + 0.5) as u32;
+        let high_b = available - high_a;
+        let hpoint_b = if high_b > 0 {
+            high_a + self.dead_time
+        } else {
+            0
+        };
+
+        let high_a = high_a.min(max_duty);
+        let high_b = high_b.min(max_duty);
+
+        if self.a.state() == ChannelState::Stopped || self.b.state() == ChannelState::Stopped {
+            // The last duties of stopped legs may stem from anywhere and
+            // overlap, so restart them with the new ones right away. Bring
+            // both legs low first so that none goes high while the other one
+            // is still braking.
+            self.a.stop(Level::Low)?;
+            self.b.stop(Level::Low)?;
+            self.a.restart_with_hpoint(high_a, 0)?;
+            self.b.restart_with_hpoint(high_b, hpoint_b)?;
+        } else if drive >= self.drive {
+            // Shrink the leg losing duty first for keeping the dead time
+            // while the updates take effect.
+            self.b.set_duty_with_hpoint(high_b, hpoint_b)?;
+            self.a.set_duty_with_hpoint(high_a, 0)?;
+        } else {
+            self.a.set_duty_with_hpoint(high_a, 0)?;
+            self.b.set_duty_with_hpoint(high_b, hpoint_b)?;
+        }
+
+        self.drive = drive;
+        Ok(())
+    }
+
+    /// Returns the last drive set
+    pub fn drive(&self) -> f32 {
+        self.drive
+    }
+
+    /// Brakes the motor by driving both legs statically high
+    ///
+    /// Whether this shorts the motor windings depends on the bridge driver,
+    /// check its truth table.
+    pub fn brake(&mut self) -> Result<(), EspError> {
+        // Bring both legs low first so that none goes high while the other
+        // one is still switching.
+        self.a.stop(Level::Low)?;
+        self.b.stop(Level::Low)?;
+        self.a.stop(Level::High)?;
+        self.b.stop(Level::High)?;
+        self.drive = 0.0;
+        Ok(())
+    }
+
+    /// Lets the motor coast by driving both legs statically low
+    pub fn coast(&mut self) -> Result<(), EspError> {
+        self.a.stop(Level::Low)?;
+        self.b.stop(Level::Low)?;
+        self.drive = 0.0;
+        Ok(())
+    }
+
+    /// Releases the two legs
+    pub fn release(self) -> (A, B) {
+        (self.a, self.b)
+    }
+
+    /// Returns the timer period in counts
+    fn period(&self) -> Result<u32, EspError> {
+        Ok(self.a.get_max_duty()? + 1)
+    }
+}
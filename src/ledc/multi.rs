@@ -0,0 +1,337 @@
+//! Channels at different frequencies on top of the four LED Control timers
+
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+use super::config::{ChannelConfig, TimerConfig};
+use super::{
+    Channel, Duty, HwChannel, HwTimer, LedcError, Timer, TimerSetupError, TIMER0, TIMER1, TIMER2,
+    TIMER3,
+};
+use crate::gpio::OutputPin;
+use crate::units::*;
+
+/// A hardware timer of a [`MultiTimer`] which is either unused or configured
+/// for one of the requested frequencies
+enum Slot<T: HwTimer> {
+    Free(T),
+    Used(Timer<T>),
+}
+
+impl<T: HwTimer> Slot<T> {
+    /// Configures the timer for `frequency` or leaves it free for `None`
+    fn allocate(
+        instance: T,
+        config: &TimerConfig,
+        frequency: Option<Hertz>,
+    ) -> Result<Self, TimerSetupError<T>> {
+        match frequency {
+            Some(frequency) => {
+                Timer::try_new(instance, &config.frequency(frequency)).map(Slot::Used)
+            }
+            None => Ok(Slot::Free(instance)),
+        }
+    }
+
+    fn timer_for(&self, frequency: Hertz) -> Option<&Timer<T>> {
+        match self {
+            Slot::Used(timer) if timer.requested_frequency() == frequency => Some(timer),
+            _ => None,
+        }
+    }
+
+    fn frequency(&self) -> Option<Hertz> {
+        match self {
+            Slot::Used(timer) => Some(timer.requested_frequency()),
+            Slot::Free(_) => None,
+        }
+    }
+
+    fn release(self) -> Result<T, EspError> {
+        match self {
+            Slot::Free(instance) => Ok(instance),
+            Slot::Used(timer) => timer.release(),
+        }
+    }
+
+    /// Stops and releases the timer while unwinding a failed setup
+    fn reclaim(self) -> T {
+        match self {
+            Slot::Free(instance) => instance,
+            Slot::Used(timer) => timer.force_release(),
+        }
+    }
+}
+
+/// Error returned by [`MultiTimer::new()`] along with the timers passed to it
+///
+/// The timers configured before the failure have been stopped and released
+/// again, so all four are handed back unconfigured.
+pub struct MultiTimerSetupError {
+    /// [`LedcError::OutOfTimers`] or the error of configuring a timer
+    pub error: LedcError,
+    pub timers: (TIMER0, TIMER1, TIMER2, TIMER3),
+}
+
+impl From<MultiTimerSetupError> for LedcError {
+    fn from(err: MultiTimerSetupError) -> Self {
+        err.error
+    }
+}
+
+/// Prints the error only
+impl core::fmt::Debug for MultiTimerSetupError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MultiTimerSetupError")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl core::fmt::Display for MultiTimerSetupError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Allocates one low speed timer per distinct frequency for driving channels
+/// at different frequencies
+///
+/// A timer determines frequency and resolution of all of its channels. So
+/// channels at different frequencies need timers of their own, and binding a
+/// channel to a timer configured for another frequency silently outputs the
+/// wrong one. This takes all four timers and configures them for the
+/// requested frequencies up front. Equal frequencies share a timer, and more
+/// than four distinct ones get rejected with [`LedcError::OutOfTimers`].
+/// Channels are then created for a frequency instead of a timer and get bound
+/// to the matching one.
+///
+/// ```
+/// use embedded_hal::pwm::blocking::PwmPin;
+/// use esp_idf_hal::ledc::{config::TimerConfig, multi::MultiTimer};
+/// use esp_idf_hal::peripherals::Peripherals;
+/// use esp_idf_hal::prelude::*;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let ledc = peripherals.ledc;
+/// let frequencies = [1.kHz().into(), 2.kHz().into(), 5.kHz().into()];
+/// let timers = MultiTimer::new(
+///     (ledc.timer0, ledc.timer1, ledc.timer2, ledc.timer3),
+///     &TimerConfig::default(),
+///     &frequencies,
+/// )?;
+///
+/// let mut a = timers.channel(ledc.channel0, frequencies[0], peripherals.pins.gpio1)?;
+/// let mut b = timers.channel(ledc.channel1, frequencies[1], peripherals.pins.gpio2)?;
+/// let mut c = timers.channel(ledc.channel2, frequencies[2], peripherals.pins.gpio3)?;
+/// a.set_duty(a.get_max_duty()? / 2)?;
+/// ```
+pub struct MultiTimer {
+    timer0: Slot<TIMER0>,
+    timer1: Slot<TIMER1>,
+    timer2: Slot<TIMER2>,
+    timer3: Slot<TIMER3>,
+}
+
+impl MultiTimer {
+    /// Configures a timer for each distinct frequency in `frequencies`
+    ///
+    /// All timers use `config` with its frequency replaced. Timers left over
+    /// remain unconfigured.
+    ///
+    /// The error carries [`LedcError::OutOfTimers`] if there are more than
+    /// four distinct frequencies and the errors of [`Timer::new()`]
+    /// otherwise. Timers already configured get stopped and released on
+    /// errors and all four are handed back with it.
+    pub fn new(
+        timers: (TIMER0, TIMER1, TIMER2, TIMER3),
+        config: &TimerConfig,
+        frequencies: &[Hertz],
+    ) -> Result<Self, MultiTimerSetupError> {
+        let mut distinct = [None; 4];
+        let mut count = 0;
+        for &frequency in frequencies {
+            if distinct[..count].contains(&Some(frequency)) {
+                continue;
+            }
+            if count == distinct.len() {
+                return Err(MultiTimerSetupError {
+                    error: LedcError::OutOfTimers,
+                    timers,
+                });
+            }
+            distinct[count] = Some(frequency);
+            count += 1;
+        }
+
+        let (timer0, timer1, timer2, timer3) = timers;
+        let timer0 = match Slot::allocate(timer0, config, distinct[0]) {
+            Ok(slot) => slot,
+            Err(err) => {
+                return Err(MultiTimerSetupError {
+                    error: err.error,
+                    timers: (err.instance, timer1, timer2, timer3),
+                })
+            }
+        };
+        let timer1 = match Slot::allocate(timer1, config, distinct[1]) {
+            Ok(slot) => slot,
+            Err(err) => {
+                return Err(MultiTimerSetupError {
+                    error: err.error,
+                    timers: (timer0.reclaim(), err.instance, timer2, timer3),
+                })
+            }
+        };
+        let timer2 = match Slot::allocate(timer2, config, distinct[2]) {
+            Ok(slot) => slot,
+            Err(err) => {
+                return Err(MultiTimerSetupError {
+                    error: err.error,
+                    timers: (timer0.reclaim(), timer1.reclaim(), err.instance, timer3),
+                })
+            }
+        };
+        let timer3 = match Slot::allocate(timer3, config, distinct[3]) {
+            Ok(slot) => slot,
+            Err(err) => {
+                return Err(MultiTimerSetupError {
+                    error: err.error,
+                    timers: (
+                        timer0.reclaim(),
+                        timer1.reclaim(),
+                        timer2.reclaim(),
+                        err.instance,
+                    ),
+                })
+            }
+        };
+
+        Ok(Self {
+            timer0,
+            timer1,
+            timer2,
+            timer3,
+        })
+    }
+
+    /// Creates a channel on `pin` bound to the timer configured for
+    /// `frequency`
+    ///
+    /// Returns [`LedcError::UnknownFrequency`] if `frequency` has not been
+    /// requested when creating the timers.
+    pub fn channel<C: HwChannel, P: OutputPin>(
+        &self,
+        instance: C,
+        frequency: Hertz,
+        pin: P,
+    ) -> Result<MultiChannel<'_, C, P>, LedcError> {
+        self.channel_with_config(instance, frequency, pin, &Default::default())
+    }
+
+    /// Creates a channel like [`channel()`](MultiTimer::channel()) using the
+    /// given channel configuration
+    pub fn channel_with_config<C: HwChannel, P: OutputPin>(
+        &self,
+        instance: C,
+        frequency: Hertz,
+        pin: P,
+        config: &ChannelConfig,
+    ) -> Result<MultiChannel<'_, C, P>, LedcError> {
+        if let Some(timer) = self.timer0.timer_for(frequency) {
+            Channel::new_with_config(instance, timer, pin, config).map(MultiChannel::Timer0)
+        } else if let Some(timer) = self.timer1.timer_for(frequency) {
+            Channel::new_with_config(instance, timer, pin, config).map(MultiChannel::Timer1)
+        } else if let Some(timer) = self.timer2.timer_for(frequency) {
+            Channel::new_with_config(instance, timer, pin, config).map(MultiChannel::Timer2)
+        } else if let Some(timer) = self.timer3.timer_for(frequency) {
+            Channel::new_with_config(instance, timer, pin, config).map(MultiChannel::Timer3)
+        } else {
+            Err(LedcError::UnknownFrequency)
+        }
+    }
+
+    /// Returns the frequencies the four timers are configured for, `None`
+    /// for the ones left unused
+    pub fn frequencies(&self) -> [Option<Hertz>; 4] {
+        [
+            self.timer0.frequency(),
+            self.timer1.frequency(),
+            self.timer2.frequency(),
+            self.timer3.frequency(),
+        ]
+    }
+
+    /// Stops the configured timers and releases all four hardware timers
+    pub fn release(self) -> Result<(TIMER0, TIMER1, TIMER2, TIMER3), EspError> {
+        Ok((
+            self.timer0.release()?,
+            self.timer1.release()?,
+            self.timer2.release()?,
+            self.timer3.release()?,
+        ))
+    }
+}
+
+/// A channel created by a [`MultiTimer`] and bound to one of its timers
+///
+/// The timers have distinct types, so this wraps the channel for the timer
+/// selected at runtime. It implements [`PwmPin`], the concrete channel can be
+/// accessed by matching on it.
+pub enum MultiChannel<'a, C: HwChannel, P: OutputPin> {
+    Timer0(Channel<'a, C, TIMER0, P>),
+    Timer1(Channel<'a, C, TIMER1, P>),
+    Timer2(Channel<'a, C, TIMER2, P>),
+    Timer3(Channel<'a, C, TIMER3, P>),
+}
+
+/// Evaluates `$body` with `$channel` bound to the channel wrapped by the
+/// [`MultiChannel`] `$multi`
+macro_rules! with_channel {
+    ($multi:expr, $channel:ident => $body:expr) => {
+        match $multi {
+            MultiChannel::Timer0($channel) => $body,
+            MultiChannel::Timer1($channel) => $body,
+            MultiChannel::Timer2($channel) => $body,
+            MultiChannel::Timer3($channel) => $body,
+        }
+    };
+}
+
+impl<'a, C: HwChannel, P: OutputPin> MultiChannel<'a, C, P> {
+    /// Returns the index of the hardware timer the channel is bound to
+    pub fn timer_index(&self) -> ledc_timer_t {
+        with_channel!(self, channel => channel.timer_index())
+    }
+
+    /// Stops the output channel and releases its hardware resource and GPIO
+    /// pin
+    pub fn release(self) -> Result<(C, P), EspError> {
+        with_channel!(self, channel => channel.release())
+    }
+}
+
+impl<'a, C: HwChannel, P: OutputPin> PwmPin for MultiChannel<'a, C, P> {
+    type Duty = Duty;
+    type Error = EspError;
+
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        with_channel!(self, channel => channel.disable())
+    }
+
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        with_channel!(self, channel => channel.enable())
+    }
+
+    fn get_duty(&self) -> Result<Self::Duty, Self::Error> {
+        with_channel!(self, channel => channel.get_duty())
+    }
+
+    fn get_max_duty(&self) -> Result<Self::Duty, Self::Error> {
+        with_channel!(self, channel => channel.get_max_duty())
+    }
+
+    fn set_duty(&mut self, duty: Duty) -> Result<(), Self::Error> {
+        with_channel!(self, channel => channel.set_duty(duty))
+    }
+}
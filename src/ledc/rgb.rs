@@ -0,0 +1,179 @@
+//! RGB LED control on top of three LED Control channels
+
+use core::borrow::Borrow;
+use core::time::Duration;
+
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+use super::{Channel, Duty, HwChannel, HwSpeedMode, HwTimer, StagedDuty, Timer};
+use crate::gpio::OutputPin;
+
+/// Default exponent of the gamma curve applied to color components
+const GAMMA: f32 = 2.2;
+
+/// A color channel of an [`RgbLed`]
+///
+/// This is implemented for [`Channel`] and combines staging duties for
+/// latching all components together with starting hardware fades.
+pub trait RgbChannel: StagedDuty + PwmPin<Duty = Duty, Error = EspError> {
+    /// Starts fading to `duty` within `duration` without waiting for the end
+    /// of the fade
+    fn fade_to(&mut self, duty: Duty, duration: Duration) -> Result<(), EspError>;
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>> RgbChannel
+    for Channel<'a, C, H, P, S, T>
+{
+    fn fade_to(&mut self, duty: Duty, duration: Duration) -> Result<(), EspError> {
+        self.fade_with_time(duty, duration)
+    }
+}
+
+/// A RGB LED driven by three LED Control channels
+///
+/// Colors are given as 8 bit components which get gamma corrected for a
+/// visually linear response. The exponent defaults to 2.2 for each component
+/// and can be changed with [`set_gamma()`](RgbLed::set_gamma()). Gamma
+/// correction requires the `std` feature, the components are mapped linearly
+/// without it.
+///
+/// New colors are staged on all three channels first and latched back to
+/// back afterwards, see [`DutyGroup`](super::DutyGroup). So the color doesn't
+/// tear when the channels share a timer, which is recommended anyway.
+///
+/// ```
+/// use core::time::Duration;
+/// use esp_idf_hal::ledc::{config::TimerConfig, rgb::RgbLed, Channel, Timer};
+/// use esp_idf_hal::peripherals::Peripherals;
+/// use esp_idf_hal::prelude::*;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let config = TimerConfig::default().frequency(25.kHz().into()).auto_resolution(true);
+/// let timer = Timer::new(peripherals.ledc.timer0, &config)?;
+/// let red = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+/// let green = Channel::new(peripherals.ledc.channel1, &timer, peripherals.pins.gpio2)?;
+/// let blue = Channel::new(peripherals.ledc.channel2, &timer, peripherals.pins.gpio3)?;
+/// let mut led = RgbLed::new(red, green, blue);
+///
+/// led.set_color(255, 128, 0)?;
+/// led.set_hsv(240.0, 1.0, 0.5)?;
+/// led.fade_to_color((0, 255, 0), Duration::from_secs(1))?;
+/// ```
+pub struct RgbLed<R: RgbChannel, G: RgbChannel, B: RgbChannel> {
+    red: R,
+    green: G,
+    blue: B,
+    gamma: [f32; 3],
+}
+
+impl<R: RgbChannel, G: RgbChannel, B: RgbChannel> RgbLed<R, G, B> {
+    /// Creates a RGB LED from its red, green and blue channels
+    pub fn new(red: R, green: G, blue: B) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            gamma: [GAMMA; 3],
+        }
+    }
+
+    /// Sets the exponents of the gamma curves applied to the red, green and
+    /// blue components
+    ///
+    /// The colors of a LED often differ in their perceived brightness
+    /// response, which can be compensated with different exponents.
+    pub fn set_gamma(&mut self, red: f32, green: f32, blue: f32) {
+        self.gamma = [red, green, blue];
+    }
+
+    /// Sets the color of the LED and applies all components together
+    pub fn set_color(&mut self, red: u8, green: u8, blue: u8) -> Result<(), EspError> {
+        let duties = self.duties(red, green, blue)?;
+
+        self.red.stage_duty(duties[0])?;
+        self.green.stage_duty(duties[1])?;
+        self.blue.stage_duty(duties[2])?;
+
+        self.red.latch_duty()?;
+        self.green.latch_duty()?;
+        self.blue.latch_duty()
+    }
+
+    /// Sets the color of the LED from hue (in degrees), saturation and value
+    ///
+    /// The hue wraps around at 360°, saturation and value are clamped to
+    /// `0.0..=1.0`. NaN is treated as zero.
+    pub fn set_hsv(&mut self, hue: f32, saturation: f32, value: f32) -> Result<(), EspError> {
+        let (red, green, blue) = hsv_to_rgb(hue, saturation, value);
+        self.set_color(red, green, blue)
+    }
+
+    /// Fades the LED to `target` (red, green and blue) within `duration`
+    /// using hardware fades
+    ///
+    /// The fades of the three components are started one after another and
+    /// this returns right away.
+    pub fn fade_to_color(
+        &mut self,
+        target: (u8, u8, u8),
+        duration: Duration,
+    ) -> Result<(), EspError> {
+        let duties = self.duties(target.0, target.1, target.2)?;
+
+        self.red.fade_to(duties[0], duration)?;
+        self.green.fade_to(duties[1], duration)?;
+        self.blue.fade_to(duties[2], duration)
+    }
+
+    /// Releases the red, green and blue channels
+    pub fn release(self) -> (R, G, B) {
+        (self.red, self.green, self.blue)
+    }
+
+    /// Returns the gamma corrected duties for the given color
+    fn duties(&self, red: u8, green: u8, blue: u8) -> Result<[Duty; 3], EspError> {
+        Ok([
+            duty(red, self.gamma[0], self.red.get_max_duty()?),
+            duty(green, self.gamma[1], self.green.get_max_duty()?),
+            duty(blue, self.gamma[2], self.blue.get_max_duty()?),
+        ])
+    }
+}
+
+/// Returns the duty for the color component `value` at the given gamma
+#[cfg_attr(not(feature = "std"), allow(unused_variables))]
+fn duty(value: u8, gamma: f32, max_duty: Duty) -> Duty {
+    let fraction = value as f32 / u8::MAX as f32;
+    #[cfg(feature = "std")]
+    let fraction = fraction.powf(gamma);
+
+    (fraction * max_duty as f32 + 0.5) as Duty
+}
+
+/// Converts a color from HSV to 8 bit RGB components
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> (u8, u8, u8) {
+    let clamp = |x: f32| if x.is_nan() { 0.0 } else { x.clamp(0.0, 1.0) };
+    // abs and rem_euclid of f32 are not available without std.
+    let hue = if hue.is_finite() { hue % 360.0 } else { 0.0 };
+    let hue = if hue < 0.0 { hue + 360.0 } else { hue };
+    let saturation = clamp(saturation);
+    let value = clamp(value);
+
+    let chroma = value * saturation;
+    let sector = hue / 60.0;
+    let distance = (sector % 2.0) - 1.0;
+    let x = chroma * (1.0 - if distance < 0.0 { -distance } else { distance });
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let offset = value - chroma;
+    let component = |c: f32| ((c + offset) * u8::MAX as f32 + 0.5) as u8;
+
+    (component(red), component(green), component(blue))
+}
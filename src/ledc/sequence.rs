@@ -0,0 +1,423 @@
+//! Fade sequences played back on a LED Control channel
+
+use core::borrow::Borrow;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::time::Duration;
+
+use esp_idf_sys::*;
+
+use super::{
+    fade_end, fade_millis, Channel, Duty, HwChannel, HwSpeedMode, HwTimer, LowSpeed, Timer,
+};
+use crate::gpio::OutputPin;
+use crate::mutex::Mutex;
+
+/// Maximum number of keyframes of a [`FadeSequence`]
+pub const MAX_KEYFRAMES: usize = 8;
+
+const CHANNELS: usize = ledc_channel_t_LEDC_CHANNEL_MAX as usize;
+const SPEED_MODES: usize = ledc_mode_t_LEDC_SPEED_MODE_MAX as usize;
+
+/// A segment of a [`FadeSequence`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Keyframe {
+    /// Fades to `duty` within `duration`
+    Fade { duty: Duty, duration: Duration },
+    /// Keeps the current duty for the given time
+    Hold(Duration),
+}
+
+/// Sequence of fades and holds for animating a channel, e.g. a status LED
+///
+/// ```
+/// use core::time::Duration;
+/// use esp_idf_hal::ledc::sequence::FadeSequence;
+///
+/// let blink = FadeSequence::new()
+///     .fade_to(255, Duration::from_millis(500))
+///     .hold(Duration::from_millis(200))
+///     .fade_to(0, Duration::from_millis(500))
+///     .repeat_forever();
+/// let mut player = channel.play_sequence(&blink)?;
+/// // Do other things
+/// player.stop()?;
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct FadeSequence {
+    keyframes: [Keyframe; MAX_KEYFRAMES],
+    len: usize,
+    overflow: bool,
+    repeat: Option<usize>,
+}
+
+impl FadeSequence {
+    /// Creates an empty sequence which gets played once
+    pub fn new() -> Self {
+        Self {
+            keyframes: [Keyframe::Hold(Duration::ZERO); MAX_KEYFRAMES],
+            len: 0,
+            overflow: false,
+            repeat: Some(0),
+        }
+    }
+
+    /// Appends a fade to `duty` within `duration`
+    pub fn fade_to(self, duty: Duty, duration: Duration) -> Self {
+        self.keyframe(Keyframe::Fade { duty, duration })
+    }
+
+    /// Appends keeping the current duty for `duration`
+    pub fn hold(self, duration: Duration) -> Self {
+        self.keyframe(Keyframe::Hold(duration))
+    }
+
+    /// Appends a keyframe
+    ///
+    /// A sequence holds up to [`MAX_KEYFRAMES`] keyframes. Playing a sequence
+    /// with more keyframes appended fails with `ESP_ERR_INVALID_SIZE`.
+    pub fn keyframe(mut self, keyframe: Keyframe) -> Self {
+        match self.keyframes.get_mut(self.len) {
+            Some(slot) => {
+                *slot = keyframe;
+                self.len += 1;
+            }
+            None => self.overflow = true,
+        }
+        self
+    }
+
+    /// Plays the sequence `count` more times after playing it once
+    pub fn repeat(mut self, count: usize) -> Self {
+        self.repeat = Some(count);
+        self
+    }
+
+    /// Plays the sequence over and over until it gets stopped
+    pub fn repeat_forever(mut self) -> Self {
+        self.repeat = None;
+        self
+    }
+
+    /// Returns the keyframes of this sequence
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes[..self.len]
+    }
+}
+
+impl Default for FadeSequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Playback state shared between a player and its `esp_timer` callback
+struct Playback {
+    speed_mode: ledc_mode_t,
+    channel: ledc_channel_t,
+    /// Keyframes with duties already converted for the hardware
+    keyframes: [Keyframe; MAX_KEYFRAMES],
+    len: usize,
+    repeat: Option<usize>,
+    index: usize,
+    remaining: Option<usize>,
+    active: bool,
+}
+
+impl Playback {
+    const fn new() -> Self {
+        Self {
+            speed_mode: 0,
+            channel: 0,
+            keyframes: [Keyframe::Hold(Duration::ZERO); MAX_KEYFRAMES],
+            len: 0,
+            repeat: Some(0),
+            index: 0,
+            remaining: Some(0),
+            active: false,
+        }
+    }
+
+    /// Returns the next keyframe and advances, wrapping around as long as
+    /// repetitions are left
+    fn advance(&mut self) -> Option<Keyframe> {
+        if self.index == self.len {
+            match &mut self.remaining {
+                Some(0) => return None,
+                Some(remaining) => *remaining -= 1,
+                None => (),
+            }
+            self.index = 0;
+        }
+
+        let keyframe = self.keyframes[self.index];
+        self.index += 1;
+        Some(keyframe)
+    }
+}
+
+struct Slot {
+    handle: AtomicPtr<esp_timer>,
+    playback: Mutex<Playback>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            handle: AtomicPtr::new(ptr::null_mut()),
+            playback: Mutex::new(Playback::new()),
+        }
+    }
+}
+
+const NEW_SLOT: Slot = Slot::new();
+const NEW_SLOTS: [Slot; CHANNELS] = [NEW_SLOT; CHANNELS];
+
+static SLOTS: [[Slot; CHANNELS]; SPEED_MODES] = [NEW_SLOTS; SPEED_MODES];
+
+/// Plays a [`FadeSequence`] on a channel it owns, as returned by
+/// [`Channel::play_sequence()`]
+///
+/// The SDK doesn't allow starting fades from the fade end interrupt. So the
+/// keyframes are advanced by an `esp_timer` callback running in the timer
+/// task. The fade end interrupt of a fade starts this timer right away, while
+/// holds are timed by the timer itself. Each fade gets started only after the
+/// previous one has ended, so the callback never waits for the SDK and
+/// doesn't stall other users of the timer task. The application isn't
+/// involved between keyframes and the hardware carries out each fade.
+pub struct SequencePlayer<
+    'a,
+    C: HwChannel,
+    H: HwTimer,
+    P: OutputPin,
+    S: HwSpeedMode = LowSpeed,
+    T = &'a Timer<H, S>,
+> where
+    T: Borrow<Timer<H, S>>,
+{
+    channel: Channel<'a, C, H, P, S, T>,
+    slot: &'static Slot,
+    handle: esp_timer_handle_t,
+}
+
+// SAFETY: The esp_timer API is thread-safe and the shared playback state is
+// guarded by a mutex.
+unsafe impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>> Send
+    for SequencePlayer<'a, C, H, P, S, T>
+where
+    Channel<'a, C, H, P, S, T>: Send,
+{
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    SequencePlayer<'a, C, H, P, S, T>
+{
+    /// Creates a player for `sequence` on `channel` without starting it
+    ///
+    /// The duties of the keyframes are clamped to the maximum duty of the
+    /// channel. Returns `ESP_ERR_INVALID_SIZE` for an empty sequence or one
+    /// with more than [`MAX_KEYFRAMES`] keyframes and `ESP_ERR_INVALID_STATE`
+    /// for a stopped channel or while dithering is enabled.
+    pub fn new(
+        mut channel: Channel<'a, C, H, P, S, T>,
+        sequence: &FadeSequence,
+    ) -> Result<Self, EspError> {
+        if sequence.len == 0 || sequence.overflow {
+            return Err(EspError::from(ESP_ERR_INVALID_SIZE as i32).unwrap());
+        }
+        channel.check_running()?;
+        channel.check_no_dither()?;
+        channel.ensure_fade_func()?;
+
+        let speed_mode = channel.timer().speed_mode;
+        let slot = &SLOTS[speed_mode as usize][C::channel() as usize];
+        {
+            let mut playback = slot.playback.lock();
+            playback.speed_mode = speed_mode;
+            playback.channel = C::channel();
+            playback.len = sequence.len;
+            playback.repeat = sequence.repeat;
+            playback.active = false;
+            for (hw, keyframe) in playback.keyframes.iter_mut().zip(sequence.keyframes()) {
+                *hw = match *keyframe {
+                    Keyframe::Fade { duty, duration } => Keyframe::Fade {
+                        duty: channel.to_hw(duty.min(channel.max_duty)),
+                        duration,
+                    },
+                    hold => hold,
+                };
+            }
+        }
+
+        let args = esp_timer_create_args_t {
+            callback: Some(callback),
+            arg: slot as *const Slot as *mut c_types::c_void,
+            dispatch_method: esp_timer_dispatch_t_ESP_TIMER_TASK,
+            name: b"ledc_sequence\0".as_ptr() as *const _,
+            ..Default::default()
+        };
+
+        let mut handle: esp_timer_handle_t = ptr::null_mut();
+        esp!(unsafe { esp_timer_create(&args, &mut handle) })?;
+        slot.handle.store(handle, Ordering::SeqCst);
+
+        Ok(Self {
+            channel,
+            slot,
+            handle,
+        })
+    }
+
+    /// Starts playing the sequence from its first keyframe, also after it
+    /// has been stopped or has ended
+    ///
+    /// The first keyframe gets started right away. If it is a fade, this
+    /// waits for a fade still in progress on the channel to end.
+    pub fn start(&mut self) -> Result<(), EspError> {
+        self.halt();
+
+        let mut playback = self.slot.playback.lock();
+        playback.index = 0;
+        playback.remaining = playback.repeat;
+        playback.active = true;
+
+        play_next(self.slot, &mut playback)
+    }
+
+    /// Aborts the sequence and takes over the duty reached so far as the
+    /// channel's duty
+    ///
+    /// A fade in progress gets stopped on ESP-IDF 5 and runs to its end on
+    /// earlier versions.
+    pub fn stop(&mut self) -> Result<(), EspError> {
+        self.halt();
+
+        #[cfg(esp_idf_version_major = "5")]
+        esp!(unsafe { ledc_fade_stop(self.channel.timer().speed_mode, C::channel()) })?;
+
+        self.channel.duty = self.channel.get_hw_duty();
+        Ok(())
+    }
+
+    /// Returns whether the sequence is still playing
+    ///
+    /// A finite sequence ends after the duration of its last keyframe.
+    pub fn is_playing(&self) -> bool {
+        self.slot.playback.lock().active
+    }
+
+    /// Returns the channel playing the sequence
+    pub fn channel(&self) -> &Channel<'a, C, H, P, S, T> {
+        &self.channel
+    }
+
+    /// Stops the sequence and releases the underlying channel
+    pub fn release(mut self) -> Result<Channel<'a, C, H, P, S, T>, EspError> {
+        self.stop()?;
+
+        // The sequence has already been stopped, so only delete its timer
+        // instead of dropping the player.
+        let this = ManuallyDrop::new(self);
+        unsafe { esp_timer_delete(this.handle) };
+        // SAFETY: The channel is moved out exactly once and the player itself
+        // won't be dropped.
+        Ok(unsafe { ptr::read(&this.channel) })
+    }
+
+    /// Deactivates the playback and stops its timer, also if it is about to
+    /// be started at the end of a fade
+    fn halt(&mut self) {
+        self.slot.playback.lock().active = false;
+        fade_end::cancel_timer(self.channel.timer().speed_mode, C::channel(), self.handle);
+    }
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>> Drop
+    for SequencePlayer<'a, C, H, P, S, T>
+{
+    fn drop(&mut self) {
+        let _ = self.stop();
+        unsafe { esp_timer_delete(self.handle) };
+    }
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    Channel<'a, C, H, P, S, T>
+{
+    /// Starts playing `sequence` on this channel, see [`SequencePlayer`]
+    ///
+    /// The returned player owns the channel until it gets released with
+    /// [`SequencePlayer::release()`]. Errors are reported as by
+    /// [`SequencePlayer::new()`].
+    pub fn play_sequence(
+        self,
+        sequence: &FadeSequence,
+    ) -> Result<SequencePlayer<'a, C, H, P, S, T>, EspError> {
+        let mut player = SequencePlayer::new(self, sequence)?;
+        player.start()?;
+        Ok(player)
+    }
+}
+
+/// Starts the next keyframe and arranges for the callback to follow up with
+/// the one after
+///
+/// A fade starts the timer with its end, a hold by its duration. The
+/// playback ends after the last keyframe or if starting a keyframe fails.
+fn play_next(slot: &Slot, playback: &mut Playback) -> Result<(), EspError> {
+    let handle = slot.handle.load(Ordering::SeqCst);
+    let (speed_mode, channel) = (playback.speed_mode, playback.channel);
+
+    let result = match playback.advance() {
+        Some(Keyframe::Fade { duty, duration }) => {
+            esp!(unsafe {
+                ledc_set_fade_with_time(speed_mode, channel, duty, fade_millis(duration))
+            })
+            .and_then(|_| {
+                // Setting up the fade has waited for a previous one to end,
+                // including its fade end callback. So this only catches the
+                // end of the new fade, which might end right away.
+                fade_end::start_timer_at_end(speed_mode, channel, handle);
+                let result = esp!(unsafe {
+                    ledc_fade_start(speed_mode, channel, ledc_fade_mode_t_LEDC_FADE_NO_WAIT)
+                });
+                if result.is_err() {
+                    fade_end::cancel_timer(speed_mode, channel, handle);
+                }
+                result
+            })
+        }
+        Some(Keyframe::Hold(duration)) => {
+            let micros = (duration.as_micros() as u64).max(1);
+            esp!(unsafe { esp_timer_start_once(handle, micros) })
+        }
+        None => {
+            playback.active = false;
+            return Ok(());
+        }
+    };
+
+    if result.is_err() {
+        playback.active = false;
+    }
+    result
+}
+
+unsafe extern "C" fn callback(arg: *mut c_types::c_void) {
+    let slot = &*(arg as *const Slot);
+    let mut playback = slot.playback.lock();
+    if !playback.active {
+        return;
+    }
+
+    // Starting a fade would restart the output of a halted channel.
+    if super::channel_halted(playback.speed_mode, playback.channel) {
+        playback.active = false;
+        return;
+    }
+
+    // Errors end the playback, which is_playing() reports.
+    let _ = play_next(slot, &mut playback);
+}
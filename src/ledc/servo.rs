@@ -0,0 +1,155 @@
+//! Hobby servo control on top of a LED Control channel
+
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+use core::borrow::Borrow;
+
+#[cfg(any(esp32, esp32s2))]
+use super::config::{ClockSource, Resolution, TimerConfig};
+use super::{Channel, HwChannel, HwSpeedMode, HwTimer, LowSpeed, Timer};
+use crate::gpio::OutputPin;
+#[cfg(any(esp32, esp32s2))]
+use crate::units::Hertz;
+
+/// Angle range covered by the pulse widths of a servo
+const MAX_ANGLE: f32 = 180.0;
+
+/// Frequency expected by typical hobby servos
+#[cfg(any(esp32, esp32s2))]
+const FREQUENCY_HZ: u32 = 50;
+
+/// Returns a timer configuration for driving servos at 50 Hz from the
+/// reference tick
+///
+/// The reference tick keeps running at 1 MHz when the APB clock gets scaled
+/// down together with the CPU clock, which would otherwise make the pulses
+/// jitter. The resolution is 14 bits, the highest one achievable from
+/// 20000 ticks per period. The divider of 1.2207 gets quantized to
+/// 1 + 56/256, which results in an actual frequency of about 50.08 Hz. One
+/// duty step then takes about 1.22 µs and a pulse range of 1000 to 2000 µs
+/// spans 819 steps, which is about 0.22° for a 180° servo.
+///
+/// ```
+/// use esp_idf_hal::ledc::{servo, Channel, Timer};
+/// use esp_idf_hal::peripherals::Peripherals;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let timer = Timer::new(peripherals.ledc.timer0, &servo::timer_config())?;
+/// let channel = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+/// let mut servo = servo::Servo::new(channel, 1000, 2000)?;
+/// assert!(servo.steps() >= 800);
+/// ```
+#[cfg(any(esp32, esp32s2))]
+pub fn timer_config() -> TimerConfig {
+    TimerConfig::default()
+        .frequency(Hertz(FREQUENCY_HZ))
+        .clock_source(ClockSource::RefTick)
+        .resolution(Resolution::Bits14)
+}
+
+/// A hobby servo driven by a LED Control channel
+///
+/// The angle gets mapped linearly to a pulse width between `min_pulse_us`
+/// (0°) and `max_pulse_us` (180°). Typical servos expect 1000 to 2000 µs at a
+/// timer frequency of 50 Hz.
+///
+/// ```
+/// use esp_idf_hal::ledc::{config::TimerConfig, servo::Servo, Channel, Timer};
+/// use esp_idf_hal::peripherals::Peripherals;
+/// use esp_idf_hal::prelude::*;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let config = TimerConfig::default().frequency(50.Hz()).auto_resolution(true);
+/// let timer = Timer::new(peripherals.ledc.timer0, &config)?;
+/// let channel = Channel::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1)?;
+/// let mut servo = Servo::new(channel, 1000, 2000)?;
+///
+/// servo.set_angle(90.0)?;
+/// ```
+pub struct Servo<
+    'a,
+    C: HwChannel,
+    H: HwTimer,
+    P: OutputPin,
+    S: HwSpeedMode = LowSpeed,
+    T = &'a Timer<H, S>,
+> where
+    T: Borrow<Timer<H, S>>,
+{
+    channel: Channel<'a, C, H, P, S, T>,
+    min_pulse_us: u32,
+    max_pulse_us: u32,
+    period_us: u32,
+}
+
+impl<'a, C: HwChannel, H: HwTimer, P: OutputPin, S: HwSpeedMode, T: Borrow<Timer<H, S>>>
+    Servo<'a, C, H, P, S, T>
+{
+    /// Creates a servo from a channel
+    ///
+    /// The period of the PWM signal is derived from the actual frequency of
+    /// the channel's timer. Returns `ESP_ERR_INVALID_ARG` if the minimum
+    /// pulse width exceeds the maximum one, if the maximum one exceeds the
+    /// period or if the period is shorter than a microsecond.
+    pub fn new(
+        channel: Channel<'a, C, H, P, S, T>,
+        min_pulse_us: u32,
+        max_pulse_us: u32,
+    ) -> Result<Self, EspError> {
+        if min_pulse_us > max_pulse_us {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        let period_us = channel
+            .timer()
+            .get_frequency()?
+            .period()
+            .map_err(|_| EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap())?
+            .as_micros() as u32;
+        if period_us == 0 || max_pulse_us > period_us {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        Ok(Self {
+            channel,
+            min_pulse_us,
+            max_pulse_us,
+            period_us,
+        })
+    }
+
+    /// Moves the servo to `degrees` within 0 to 180°
+    ///
+    /// Angles outside this range get clamped and NaN is treated as 0°.
+    pub fn set_angle(&mut self, degrees: f32) -> Result<(), EspError> {
+        let degrees = if degrees.is_nan() {
+            0.0
+        } else {
+            degrees.clamp(0.0, MAX_ANGLE)
+        };
+
+        let range_us = (self.max_pulse_us - self.min_pulse_us) as f32;
+        let pulse_us = self.min_pulse_us as f32 + range_us * degrees / MAX_ANGLE;
+        let duty = self.channel.timer().duty_for_pulse_us(pulse_us)?;
+
+        self.channel.set_duty(duty)
+    }
+
+    /// Returns the number of distinct duty steps between the minimum and the
+    /// maximum pulse width
+    ///
+    /// This is the number of positions the servo can be moved to. It depends
+    /// on the resolution of the timer, which should be chosen for at least a
+    /// few hundred steps.
+    pub fn steps(&self) -> u32 {
+        let counts = (self.channel.max_duty + 1) as u64;
+        let range_us = (self.max_pulse_us - self.min_pulse_us) as u64;
+        (range_us * counts / self.period_us as u64) as u32
+    }
+
+    /// Releases the underlying channel
+    pub fn release(self) -> Channel<'a, C, H, P, S, T> {
+        self.channel
+    }
+}
@@ -0,0 +1,321 @@
+//! Software PWM fallback for pins which can't be routed to the LED Control
+//! peripheral
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicU32, Ordering};
+
+use embedded_hal::pwm::blocking::PwmPin;
+use esp_idf_sys::*;
+
+use super::Duty;
+use crate::gpio::OutputPin;
+use crate::mutex::Mutex;
+use crate::units::*;
+
+/// Maximum duty of a [`SoftPwm`], i.e. its resolution is 8 bit
+pub const SOFT_PWM_MAX_DUTY: Duty = u8::MAX as Duty;
+
+/// Number of software PWM outputs which can exist at the same time
+const SLOTS: usize = 4;
+
+/// Number of bits of a callback argument for the slot index, the remaining
+/// ones hold the generation of the slot
+const SLOT_BITS: usize = 2;
+
+/// Shortest high or low phase in microseconds. Shorter phases are output as
+/// a static level as the `esp_timer` can't reliably dispatch callbacks much
+/// faster than this.
+const MIN_PHASE_US: u32 = 50;
+
+/// State shared between a software PWM output and its `esp_timer` callback
+struct State {
+    claimed: AtomicBool,
+    /// Generation of the slot which gets advanced when deleting its timer
+    ///
+    /// The callback holds the lock while it runs and ignores arguments from
+    /// an older generation. So once the timer got deleted under the lock, a
+    /// callback dispatched right before neither rearms the deleted timer nor
+    /// touches the output of a new owner of the slot.
+    generation: Mutex<usize>,
+    handle: AtomicPtr<esp_timer>,
+    gpio: AtomicI32,
+    high_us: AtomicU32,
+    low_us: AtomicU32,
+    enabled: AtomicBool,
+    level: AtomicBool,
+    running: AtomicBool,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            claimed: AtomicBool::new(false),
+            generation: Mutex::new(0),
+            handle: AtomicPtr::new(ptr::null_mut()),
+            gpio: AtomicI32::new(0),
+            high_us: AtomicU32::new(0),
+            low_us: AtomicU32::new(0),
+            enabled: AtomicBool::new(false),
+            level: AtomicBool::new(false),
+            running: AtomicBool::new(false),
+        }
+    }
+}
+
+const NEW_STATE: State = State::new();
+
+static STATES: [State; SLOTS] = [NEW_STATE; SLOTS];
+
+/// PWM output toggling a GPIO from an `esp_timer` callback
+///
+/// Some GPIOs can't be routed to the LED Control peripheral and the number
+/// of hardware channels is limited. This implements [`PwmPin`] as well and
+/// allows drivers generic over PWM outputs to use such pins for simple tasks
+/// like dimming a LED.
+///
+/// Each phase of the signal gets timed by the `esp_timer` task, so the output
+/// jitters with the load of the system and costs CPU time for every edge.
+/// Toggling from an ISR would be more accurate, but dispatching `esp_timer`
+/// callbacks from an ISR is an optional SDK configuration
+/// (`CONFIG_ESP_TIMER_SUPPORTS_ISR_DISPATCH_METHOD`) and a general purpose
+/// timer would be taken away from the application. The `esp_timer` task is
+/// always available and allows the callback to synchronize with releasing
+/// the output.
+/// Phases shorter than 50 µs are output as a static level. So the frequency
+/// should stay in the range of a few hundred hertz and the effective
+/// resolution is far less than the 8 bit of the duty scale. Up to four
+/// software PWM outputs can exist at the same time.
+///
+/// ```
+/// use esp_idf_hal::ledc::soft::SoftPwm;
+/// use esp_idf_hal::peripherals::Peripherals;
+/// use esp_idf_hal::prelude::*;
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let mut led = SoftPwm::new(peripherals.pins.gpio5, 200.Hz().into())?;
+/// led.set_duty(led.get_max_duty()? / 4)?;
+/// ```
+pub struct SoftPwm<P: OutputPin> {
+    pin: P,
+    state: &'static State,
+    handle: esp_timer_handle_t,
+    period_us: u32,
+    duty: Duty,
+}
+
+// SAFETY: The esp_timer API is thread-safe and the shared state consists of
+// atomics only.
+unsafe impl<P: OutputPin> Send for SoftPwm<P> {}
+
+impl<P: OutputPin> SoftPwm<P> {
+    /// Creates a software PWM output at `frequency` on `pin` which starts
+    /// with a duty of zero
+    ///
+    /// Returns `ESP_ERR_INVALID_ARG` if the period at `frequency` doesn't
+    /// span two minimum phases and `ESP_ERR_NO_MEM` if all software PWM
+    /// outputs are in use.
+    pub fn new(pin: P, frequency: Hertz) -> Result<Self, EspError> {
+        let period_us = frequency
+            .period()
+            .map(|period| period.as_micros() as u32)
+            .unwrap_or(0);
+        if period_us < 2 * MIN_PHASE_US {
+            return Err(EspError::from(ESP_ERR_INVALID_ARG as i32).unwrap());
+        }
+
+        let (slot, state) = STATES
+            .iter()
+            .enumerate()
+            .find(|(_, state)| {
+                state
+                    .claimed
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            })
+            .ok_or_else(|| EspError::from(ESP_ERR_NO_MEM as i32).unwrap())?;
+
+        match Self::create_timer(&pin, slot, state) {
+            Ok(handle) => Ok(Self {
+                pin,
+                state,
+                handle,
+                period_us,
+                duty: 0,
+            }),
+            Err(err) => {
+                state.claimed.store(false, Ordering::SeqCst);
+                Err(err)
+            }
+        }
+    }
+
+    /// Stops the output and releases the pin
+    pub fn release(mut self) -> P {
+        self.shutdown();
+
+        // The output has already been shut down, so skip doing this once more
+        // when dropping it.
+        let this = core::mem::ManuallyDrop::new(self);
+        // SAFETY: The pin is moved out exactly once and the output itself
+        // won't be dropped.
+        unsafe { ptr::read(&this.pin) }
+    }
+
+    fn create_timer(
+        pin: &P,
+        slot: usize,
+        state: &'static State,
+    ) -> Result<esp_timer_handle_t, EspError> {
+        esp!(unsafe { gpio_reset_pin(pin.pin()) })?;
+        esp!(unsafe { gpio_set_direction(pin.pin(), gpio_mode_t_GPIO_MODE_OUTPUT) })?;
+        esp!(unsafe { gpio_set_level(pin.pin(), 0) })?;
+
+        state.gpio.store(pin.pin(), Ordering::SeqCst);
+        state.high_us.store(0, Ordering::SeqCst);
+        state.low_us.store(0, Ordering::SeqCst);
+        state.enabled.store(true, Ordering::SeqCst);
+        state.level.store(false, Ordering::SeqCst);
+        state.running.store(false, Ordering::SeqCst);
+
+        // Callbacks of a previous owner of the slot carry an older
+        // generation.
+        let generation = *state.generation.lock();
+        let args = esp_timer_create_args_t {
+            callback: Some(callback),
+            arg: ((generation << SLOT_BITS) | slot) as *mut c_types::c_void,
+            dispatch_method: esp_timer_dispatch_t_ESP_TIMER_TASK,
+            name: b"soft_pwm\0".as_ptr() as *const _,
+            ..Default::default()
+        };
+
+        let mut handle: esp_timer_handle_t = ptr::null_mut();
+        esp!(unsafe { esp_timer_create(&args, &mut handle) })?;
+        state.handle.store(handle, Ordering::SeqCst);
+
+        Ok(handle)
+    }
+
+    /// Lets the callback pick up the current state unless it is already
+    /// toggling the output
+    fn kick(&self) -> Result<(), EspError> {
+        if !self.state.running.swap(true, Ordering::SeqCst) {
+            if let Err(err) = esp!(unsafe { esp_timer_start_once(self.handle, 1) }) {
+                self.state.running.store(false, Ordering::SeqCst);
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        self.state.enabled.store(false, Ordering::SeqCst);
+
+        {
+            // The callback can't rearm the timer while the lock is held, so
+            // it is stopped for good before deleting it.
+            let mut generation = self.state.generation.lock();
+            unsafe {
+                esp_timer_stop(self.handle);
+                esp_timer_delete(self.handle);
+            }
+            self.state.handle.store(ptr::null_mut(), Ordering::SeqCst);
+            *generation = generation.wrapping_add(1) & (usize::MAX >> SLOT_BITS);
+        }
+
+        unsafe { gpio_set_level(self.pin.pin(), 0) };
+        self.state.claimed.store(false, Ordering::SeqCst);
+    }
+}
+
+impl<P: OutputPin> Drop for SoftPwm<P> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl<P: OutputPin> PwmPin for SoftPwm<P> {
+    type Duty = Duty;
+    type Error = EspError;
+
+    /// Stops toggling and drives the pin low
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        self.state.enabled.store(false, Ordering::SeqCst);
+        self.kick()
+    }
+
+    /// Resumes toggling with the last duty set
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.state.enabled.store(true, Ordering::SeqCst);
+        self.kick()
+    }
+
+    fn get_duty(&self) -> Result<Self::Duty, Self::Error> {
+        Ok(self.duty)
+    }
+
+    fn get_max_duty(&self) -> Result<Self::Duty, Self::Error> {
+        Ok(SOFT_PWM_MAX_DUTY)
+    }
+
+    /// Sets the duty cycle, which takes effect with the next edge
+    ///
+    /// Values beyond [`get_max_duty()`](PwmPin::get_max_duty()) saturate at
+    /// the maximum duty.
+    fn set_duty(&mut self, duty: Duty) -> Result<(), Self::Error> {
+        let duty = duty.min(SOFT_PWM_MAX_DUTY);
+        let high_us = (self.period_us as u64 * duty as u64 / SOFT_PWM_MAX_DUTY as u64) as u32;
+        let low_us = self.period_us - high_us;
+
+        self.state.high_us.store(high_us, Ordering::SeqCst);
+        self.state.low_us.store(low_us, Ordering::SeqCst);
+        self.duty = duty;
+
+        self.kick()
+    }
+}
+
+unsafe extern "C" fn callback(arg: *mut c_types::c_void) {
+    let state = &STATES[arg as usize & ((1 << SLOT_BITS) - 1)];
+    let generation = state.generation.lock();
+    if *generation != (arg as usize) >> SLOT_BITS {
+        // The timer this has been dispatched for got deleted meanwhile.
+        return;
+    }
+
+    let gpio = state.gpio.load(Ordering::SeqCst);
+    let high_us = state.high_us.load(Ordering::SeqCst);
+    let low_us = state.low_us.load(Ordering::SeqCst);
+    let enabled = state.enabled.load(Ordering::SeqCst);
+
+    // Output a static level and stop toggling until the next kick.
+    let level = if !enabled || high_us < MIN_PHASE_US {
+        Some(false)
+    } else if low_us < MIN_PHASE_US {
+        Some(true)
+    } else {
+        None
+    };
+    if let Some(level) = level {
+        gpio_set_level(gpio, level as u32);
+        state.level.store(level, Ordering::SeqCst);
+        state.running.store(false, Ordering::SeqCst);
+
+        // A new duty may have been set after loading the phases but before
+        // clearing running, which didn't kick the timer then.
+        let changed = state.high_us.load(Ordering::SeqCst) != high_us
+            || state.low_us.load(Ordering::SeqCst) != low_us
+            || state.enabled.load(Ordering::SeqCst) != enabled;
+        if changed && !state.running.swap(true, Ordering::SeqCst) {
+            esp_timer_start_once(state.handle.load(Ordering::SeqCst), 1);
+        }
+        return;
+    }
+
+    let level = !state.level.load(Ordering::SeqCst);
+    gpio_set_level(gpio, level as u32);
+    state.level.store(level, Ordering::SeqCst);
+
+    let timeout = if level { high_us } else { low_us };
+    esp_timer_start_once(state.handle.load(Ordering::SeqCst), timeout as u64);
+}
@@ -31,6 +31,8 @@ pub mod i2c;
 #[cfg(all(feature = "experimental", not(feature = "riscv-ulp-hal")))]
 pub mod interrupt;
 #[cfg(not(feature = "riscv-ulp-hal"))]
+pub mod ledc;
+#[cfg(not(feature = "riscv-ulp-hal"))]
 pub mod mutex;
 pub mod peripherals;
 pub mod prelude;
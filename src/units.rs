@@ -16,6 +16,7 @@
 use core::convert::TryFrom;
 use core::convert::TryInto;
 use core::fmt;
+use core::time::Duration;
 
 pub type ValueType = u32;
 pub type LargeValueType = u64;
@@ -332,3 +333,84 @@ divide!(
     (KiloHertz, KiloHertzU64, NanoSeconds, NanoSecondsU64, 1_000_000     ),
     (MegaHertz, MegaHertzU64, NanoSeconds, NanoSecondsU64, 1_000         )
 );
+
+/// Error of a conversion between frequency and period involving a frequency
+/// of zero, either given or resulting from rounding a long period
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ZeroFrequencyError;
+
+impl fmt::Display for ZeroFrequencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "zero frequency")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ZeroFrequencyError {}
+
+impl Hertz {
+    /// Returns the period of one cycle, rounded to the nearest nanosecond
+    pub fn period(&self) -> Result<Duration, ZeroFrequencyError> {
+        if self.0 == 0 {
+            return Err(ZeroFrequencyError);
+        }
+
+        let hz = LargeValueType::from(self.0);
+        Ok(Duration::from_nanos((1_000_000_000 + hz / 2) / hz))
+    }
+
+    /// Returns the frequency with the given period, rounded to the nearest
+    /// hertz
+    pub fn from_period(period: Duration) -> Result<Self, ZeroFrequencyError> {
+        let nanos = period.as_nanos();
+        if nanos == 0 {
+            return Err(ZeroFrequencyError);
+        }
+
+        match (1_000_000_000 + nanos / 2) / nanos {
+            0 => Err(ZeroFrequencyError),
+            hz => Ok(Hertz(hz as ValueType)),
+        }
+    }
+}
+
+impl TryFrom<Duration> for Hertz {
+    type Error = ZeroFrequencyError;
+
+    fn try_from(period: Duration) -> Result<Self, Self::Error> {
+        Hertz::from_period(period)
+    }
+}
+
+/// Duty cycle in percent, converted to counts against the current maximum
+/// duty by the driver using it
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Hash, Default, Debug)]
+pub struct DutyPercent(pub u8);
+
+pub trait FromValueTypePercent {
+    fn percent(self) -> DutyPercent;
+}
+
+impl FromValueTypePercent for u8 {
+    fn percent(self) -> DutyPercent {
+        DutyPercent(self)
+    }
+}
+
+impl From<u8> for DutyPercent {
+    fn from(x: u8) -> Self {
+        DutyPercent(x)
+    }
+}
+
+impl From<DutyPercent> for u8 {
+    fn from(x: DutyPercent) -> Self {
+        x.0
+    }
+}
+
+impl fmt::Display for DutyPercent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}